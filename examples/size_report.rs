@@ -0,0 +1,51 @@
+use gen_dylib::{ImportLibBuilder, TargetArch};
+
+const ARCHIVE_HEADER_LEN: usize = 60;
+const SYMBOL_LEN: usize = 18;
+
+fn read_u32(data: &[u8], at: usize) -> usize {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as usize
+}
+
+// the bytes the string table would grow by if the inlined symbol names of the COFF object
+// `member` were stored there instead, with their NUL
+fn inlined_name_bytes(member: &[u8]) -> usize {
+    let symbol_table = read_u32(member, 8);
+    let symbols = read_u32(member, 12);
+
+    (0..symbols)
+        .map(|i| &member[symbol_table + i * SYMBOL_LEN..][..8])
+        .filter(|name| name[..4] != [0; 4])
+        .map(|name| name.iter().take_while(|&&b| b != 0).count() + 1)
+        .sum()
+}
+
+fn main() {
+    for &count in &[10, 1_000, 10_000] {
+        let builder = (0..count).fold(
+            ImportLibBuilder::new("mydll.dll").target_arch(TargetArch::X86_64),
+            |builder, i| {
+                let name = format!("func_{}", i);
+                builder.import_name(&name, &name)
+            },
+        );
+
+        let report = builder.size_report();
+        println!("{} imports: {} bytes", count, report.total());
+        println!("{:#?}", report);
+        println!("short imports average {} bytes each", report.short_imports / count);
+
+        // the descriptors directly follow the linker members
+        let lib = builder.build();
+        let mut start = report.signature + report.first_linker_member + report.second_linker_member;
+        let mut inlined = 0;
+        let mut not_inlined = 0;
+        for &size in &[report.import_descriptor, report.null_import_descriptor, report.null_thunk_data] {
+            let member = &lib[start + ARCHIVE_HEADER_LEN..start + size];
+            inlined += size;
+            not_inlined += size + inlined_name_bytes(member);
+            start += size;
+        }
+        println!("descriptors: {} bytes with short names inlined, {} without", inlined, not_inlined);
+    }
+}
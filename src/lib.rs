@@ -1,8 +1,19 @@
-use byteorder::{NativeEndian, BigEndian, WriteBytesExt};
-use std::io::{self, Write};
+use byteorder::{ByteOrder, NativeEndian, BigEndian, WriteBytesExt};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
 
 use indexmap::IndexMap;
 
+mod parse;
+#[cfg(any(test, feature = "debug_verify"))]
+mod verify;
+
+pub use parse::{detected_arch, parse_linker_members, parse_short_import, FirstLinkerMember, SecondLinkerMember, ParseError};
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Import {
     Name(String),
@@ -25,10 +36,319 @@ impl Import {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportKind {
+    Code,
+    Data,
+    Const,
+}
+
+impl ImportKind {
+    fn bits(self) -> u16 {
+        match self {
+            ImportKind::Code => 0x0,  // IMPORT_CODE
+            ImportKind::Data => 0x1,  // IMPORT_DATA
+            ImportKind::Const => 0x2, // IMPORT_CONST
+        }
+    }
+
+    fn from_bits(bits: u16) -> Option<Self> {
+        [ImportKind::Code, ImportKind::Data, ImportKind::Const].iter().copied().find(|kind| kind.bits() == bits)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportNameType {
+    /// Imported by ordinal, the name is only used for the symbol.
+    Ordinal,
+    /// The export name is the symbol name as is.
+    Name,
+    /// The symbol name without a leading `?`, `@` or `_`.
+    NameNoPrefix,
+    /// The symbol name without its leading prefix and anything after the first `@`.
+    NameUndecorate,
+    /// The export name is stored separately, after the DLL name.
+    ExportAs,
+}
+
+impl ImportNameType {
+    fn bits(self) -> u16 {
+        match self {
+            ImportNameType::Ordinal => 0x0,        // IMPORT_OBJECT_ORDINAL
+            ImportNameType::Name => 0x1,           // IMPORT_OBJECT_NAME
+            ImportNameType::NameNoPrefix => 0x2,   // IMPORT_OBJECT_NAME_NO_PREFIX
+            ImportNameType::NameUndecorate => 0x3, // IMPORT_OBJECT_NAME_UNDECORATE
+            ImportNameType::ExportAs => 0x4,       // IMPORT_OBJECT_NAME_EXPORTAS
+        }
+    }
+
+    fn from_bits(bits: u16) -> Option<Self> {
+        [
+            ImportNameType::Ordinal,
+            ImportNameType::Name,
+            ImportNameType::NameNoPrefix,
+            ImportNameType::NameUndecorate,
+            ImportNameType::ExportAs,
+        ].iter().copied().find(|name_type| name_type.bits() == bits)
+    }
+}
+
+// the Type field of the short import header: the kind in bits 0-1, the name type in bits 2-4
+fn import_object_type(kind: ImportKind, name_type: ImportNameType) -> u16 {
+    kind.bits() | (name_type.bits() << 2)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ImportEntry {
+    rust_name: String,
+    import: Import,
+    kind: ImportKind,
+    timestamp: Option<u32>,
+    // `dll.function` an export is forwarded to, kept for callers only
+    forward_target: Option<String>,
+    // pinned name type, otherwise picked from the import
+    name_type: Option<ImportNameType>,
+    // OrdinalOrHint of a name import
+    hint: Option<u16>,
+    // caller defined grouping, never emitted
+    region: Option<String>,
+    member_name: Option<String>,
+    // header name field kept as is from a parsed lib
+    header_name: Option<String>,
+}
+
+impl ImportEntry {
+    fn new(rust_name: &str, import: Import) -> Self {
+        Self {
+            rust_name: rust_name.to_owned(),
+            import,
+            kind: ImportKind::Code,
+            timestamp: None,
+            forward_target: None,
+            name_type: None,
+            hint: None,
+            region: None,
+            member_name: None,
+            header_name: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ObjectMember {
+    data: Vec<u8>,
+    symbols: Vec<String>,
+    // header name field kept as is from a parsed lib
+    header_name: Option<String>,
+}
+
+/// The order of the short import members, which otherwise follows insertion order.
+#[derive(Clone, Copy, Debug)]
+pub enum MemberOrder {
+    Insertion,
+    /// Sorted by rust name.
+    ByName,
+    /// Ordinal imports sorted by ordinal, followed by the name imports in insertion order.
+    ByOrdinal,
+    /// Sorted by a comparator over `(rust_name, import)` pairs.
+    Custom(fn((&str, &Import), (&str, &Import)) -> Ordering),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArSymbolTable {
+    /// The two linker members `lib.exe` writes.
+    Msvc,
+    /// The single big-endian `/` symbol index written by GNU `ar`.
+    Gnu,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetArch {
+    X86,
+    X86_64,
+    AArch64,
+}
+
+impl TargetArch {
+    fn host() -> Self {
+        if cfg!(target_arch = "x86_64") {
+            TargetArch::X86_64
+        } else if cfg!(target_arch = "x86") {
+            TargetArch::X86
+        } else if cfg!(target_arch = "aarch64") {
+            TargetArch::AArch64
+        } else {
+            panic!("unsupported arch")
+        }
+    }
+
+    /// Maps a rustc target triple, such as cargo's `TARGET` in a build script, to its arch.
+    /// Only the arch component matters: `x86_64-unknown-uefi` produces the same lib as
+    /// `x86_64-pc-windows-msvc`, since the subsystem is chosen by the linker, not the import lib.
+    pub fn from_target_triple(triple: &str) -> Option<Self> {
+        match triple.split('-').next()? {
+            "x86_64" => Some(TargetArch::X86_64),
+            "i386" | "i586" | "i686" => Some(TargetArch::X86),
+            "aarch64" => Some(TargetArch::AArch64),
+            _ => None,
+        }
+    }
+
+    fn machine(self) -> u16 {
+        match self {
+            TargetArch::X86 => 0x014C,     // IMAGE_FILE_MACHINE_I386
+            TargetArch::X86_64 => 0x8664,  // IMAGE_FILE_MACHINE_AMD64
+            TargetArch::AArch64 => 0xAA64, // IMAGE_FILE_MACHINE_ARM64
+        }
+    }
+
+    fn from_machine(machine: u16) -> Option<Self> {
+        match machine {
+            0x014C => Some(TargetArch::X86),
+            0x8664 => Some(TargetArch::X86_64),
+            0xAA64 => Some(TargetArch::AArch64),
+            _ => None,
+        }
+    }
+
+    fn pointer_size(self) -> usize {
+        match self {
+            TargetArch::X86 => 4,
+            TargetArch::X86_64 | TargetArch::AArch64 => 8,
+        }
+    }
+
+    fn rva_relocation(self) -> u16 {
+        match self {
+            TargetArch::X86 => 0x07,     // IMAGE_REL_I386_DIR32NB
+            TargetArch::X86_64 => 0x03,  // IMAGE_REL_AMD64_ADDR32NB
+            TargetArch::AArch64 => 0x02, // IMAGE_REL_ARM64_ADDR32NB
+        }
+    }
+
+    fn file_characteristics(self) -> u16 {
+        match self {
+            TargetArch::X86 => 0x0100, // IMAGE_FILE_32BIT_MACHINE
+            TargetArch::X86_64 | TargetArch::AArch64 => 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NormalizeReport {
+    /// Imports dropped because an earlier import had the same rust name.
+    pub removed: Vec<(String, Import)>,
+    /// Rust names importing ordinal 0, which no DLL can export.
+    pub invalid_ordinals: Vec<String>,
+}
+
+/// Adds `new_imports` to the lib in `existing`, keeping its DLL name, arch and descriptor
+/// setup. See [`ImportLibBuilder::from_bytes`] for what carries over.
+pub fn append_to(existing: &[u8], new_imports: &[(String, Import)]) -> io::Result<Vec<u8>> {
+    let builder = ImportLibBuilder::from_bytes(existing)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut names: HashSet<_> = builder.imports.iter().map(|entry| entry.rust_name.clone()).collect();
+    for (rust_name, _) in new_imports {
+        if !names.insert(rust_name.clone()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                format!("`{}` is already imported", rust_name)));
+        }
+    }
+
+    new_imports.iter()
+        .fold(builder, |this, (rust_name, import)| this.add_import(rust_name, import.clone()))
+        .try_build()
+}
+
+/// The short import member `ImportLibBuilder` would emit for a single code import, without
+/// the archive header around it.
+pub fn short_import_object(dll: &str, rust_name: &str, import: &Import, arch: TargetArch) -> Vec<u8> {
+    let mut archive_builder = CoffArchiveBuilder::new(dll, arch);
+    archive_builder.add_short_import(&ImportEntry::new(rust_name, import.clone())).unwrap();
+    archive_builder.sections.pop().unwrap()
+}
+
+/// The search type of a weak external, used when no other object defines the symbol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeakSearch {
+    /// Don't search libraries, resolve straight to the default symbol.
+    NoLibrary,
+    /// Search libraries for a definition before using the default symbol.
+    Library,
+    /// The symbol is an alias of the default symbol.
+    Alias,
+}
+
+impl WeakSearch {
+    pub fn characteristics(self) -> u32 {
+        match self {
+            WeakSearch::NoLibrary => 1, // IMAGE_WEAK_EXTERN_SEARCH_NOLIBRARY
+            WeakSearch::Library => 2,   // IMAGE_WEAK_EXTERN_SEARCH_LIBRARY
+            WeakSearch::Alias => 3,     // IMAGE_WEAK_EXTERN_SEARCH_ALIAS
+        }
+    }
+}
+
+/// Advisory findings from [`ImportLibBuilder::lint`]. None of them stop a build.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LintWarning {
+    /// A name import containing `@`, which looks like a decorated `__stdcall` or `__fastcall`
+    /// name, while the export is matched exactly.
+    DecoratedName { rust_name: String, name: String },
+    /// A name import starting with `?`, which looks like a mangled C++ name, while the export is
+    /// matched exactly.
+    CppMangledName { rust_name: String, name: String },
+}
+
+/// Bytes each part of the archive takes up, including member headers and padding.
+/// Components the builder doesn't emit are 0.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SizeReport {
+    pub signature: usize,
+    pub first_linker_member: usize,
+    pub second_linker_member: usize,
+    pub import_descriptor: usize,
+    pub null_import_descriptor: usize,
+    pub null_thunk_data: usize,
+    /// Members added with `add_object`, `add_metadata_comment` and `weak_alias`, and those
+    /// kept from a parsed lib.
+    pub objects: usize,
+    pub short_imports: usize,
+}
+
+impl SizeReport {
+    pub fn total(&self) -> usize {
+        self.signature
+            + self.first_linker_member
+            + self.second_linker_member
+            + self.import_descriptor
+            + self.null_import_descriptor
+            + self.null_thunk_data
+            + self.objects
+            + self.short_imports
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ImportLibBuilder {
     name: String,
-    imports: Vec<(String, Import)>,
+    imports: Vec<ImportEntry>,
+    objects: Vec<ObjectMember>,
+    comments: Vec<String>,
+    weak_aliases: Vec<(String, String, WeakSearch)>,
+    member_name: Option<String>,
+    arch: Option<TargetArch>,
+    null_import_descriptor: bool,
+    member_alignment: usize,
+    pad_final_member: bool,
+    kernel_mode: bool,
+    manual_descriptors: bool,
+    timestamp: u32,
+    symbol_table: ArSymbolTable,
+    member_order: MemberOrder,
+    // `//` member of a parsed lib, emitted right after the linker members
+    long_names: Option<Vec<u8>>,
 }
 
 impl ImportLibBuilder {
@@ -36,183 +356,1024 @@ impl ImportLibBuilder {
         Self {
             name: lib_name.to_string(),
             imports: vec![],
+            objects: vec![],
+            comments: vec![],
+            weak_aliases: vec![],
+            member_name: None,
+            arch: None,
+            null_import_descriptor: true,
+            member_alignment: 2,
+            pad_final_member: true,
+            kernel_mode: false,
+            manual_descriptors: false,
+            timestamp: 0,
+            symbol_table: ArSymbolTable::Msvc,
+            member_order: MemberOrder::Insertion,
+            long_names: None,
         }
     }
 
-    pub fn import_name(self, rust_name: &str, lib_name: &str) -> Self {
+    /// Reads imports from csv with a header row and the columns `rust_name, lib_name, ordinal, kind`.
+    /// Rows with an ordinal import by ordinal, others by `lib_name` (or `rust_name` if that's
+    /// empty too). `kind` is `code`, `data` or `const`, and defaults to `code`.
+    ///
+    /// A `lib_name` that differs from `rust_name` is a rename, emitted as an EXPORTAS member
+    /// like [`import_name`](Self::import_name) does.
+    #[cfg(feature = "csv")]
+    pub fn from_csv<R: io::Read>(lib_name: &str, reader: R) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .flexible(true)
+            .from_reader(reader);
+
+        let mut this = Self::new(lib_name);
+        for record in reader.records() {
+            let record = record?;
+            let field = |i| record.get(i).unwrap_or("");
+
+            let rust_name = field(0);
+            this = match (field(1), field(2)) {
+                ("", "") => this.import_name(rust_name, rust_name),
+                (lib_name, "") => this.import_name(rust_name, lib_name),
+                (_, ordinal) => {
+                    let ordinal = ordinal.parse()
+                        .map_err(|_| invalid(format!("invalid ordinal `{}` for `{}`", ordinal, rust_name)))?;
+                    this.import_ordinal(rust_name, ordinal)
+                }
+            };
+
+            let kind = match field(3) {
+                "" | "code" => ImportKind::Code,
+                "data" => ImportKind::Data,
+                "const" => ImportKind::Const,
+                kind => return Err(invalid(format!("unknown import kind `{}` for `{}`", kind, rust_name))),
+            };
+            this = this.as_kind(kind);
+        }
+
+        Ok(this)
+    }
+
+    /// Imports every entry of `map` from `dll`. The map is unordered, so the imports are added
+    /// sorted by rust name to keep the output reproducible.
+    pub fn from_map(dll: &str, map: HashMap<String, Import>) -> Self {
+        let mut imports: Vec<_> = map.into_iter().collect();
+        imports.sort_by(|a, b| a.0.cmp(&b.0));
+
+        imports.into_iter().fold(Self::new(dll), |this, (rust_name, import)| this.add_import(&rust_name, import))
+    }
+
+    /// Reads the imports back out of a short import lib, so more can be added to it. The
+    /// descriptor members are regenerated when building. Any other member, such as `.drectve`
+    /// objects or a long names table, is kept as is and emitted again, ahead of the imports.
+    /// The member alignment, final padding, timestamps, kernel mode and member names are read
+    /// back from the members, so an unchanged lib builds to the same bytes.
+    /// All short imports must be from the same dll, otherwise this fails with `MixedDlls`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ParseError> {
+        let mut members = parse::Members::new(bytes).ok_or(ParseError::NotAnArchive)?;
+        let first = match members.next() {
+            Some(("/", data)) => parse::parse_first_linker_member(data)?,
+            _ => return Err(ParseError::MissingLinkerMember),
+        };
+
+        let mut defined_at: HashMap<usize, Vec<&str>> = HashMap::new();
+        for (symbol, offset) in first.symbols.iter().zip(&first.offsets) {
+            defined_at.entry(*offset as usize).or_default().push(symbol);
+        }
+
+        let mut this: Option<Self> = None;
+        let mut symbol_table = ArSymbolTable::Gnu;
+        let mut long_names = None;
+        let mut passthrough = vec![];
+        let mut descriptor = None;
+        // where every member but the first starts, and where the last import ends unpadded
+        let mut member_starts = vec![];
+        let mut imports_end = 0;
+
+        while let Some((offset, name, data)) = members.next_with_offset() {
+            let defined = defined_at.remove(&offset).unwrap_or_default();
+            member_starts.push(offset);
+
+            match name {
+                "/" => symbol_table = ArSymbolTable::Msvc,
+                "//" => long_names = Some(data.to_vec()),
+                _ => {
+                    let raw = match parse::raw_short_import(data) {
+                        Ok(raw) => raw,
+                        Err(ParseError::NotShortImport) => {
+                            if defined.iter().any(|symbol| symbol.starts_with("__IMPORT_DESCRIPTOR_")) {
+                                descriptor = Some((name, data));
+                            } else if !defined.iter().any(|symbol| is_descriptor_symbol(symbol)) {
+                                passthrough.push(ObjectMember {
+                                    data: data.to_vec(),
+                                    symbols: defined.iter().map(|symbol| symbol.to_string()).collect(),
+                                    header_name: Some(name.to_owned()),
+                                });
+                            }
+                            continue;
+                        }
+                        Err(e) => return Err(e),
+                    };
+
+                    let kind = raw.kind()?;
+                    let name_type = raw.name_type()?;
+
+                    // the name string is the symbol, which is the rust name
+                    let mut entry = match name_type {
+                        ImportNameType::Ordinal => ImportEntry::new(raw.name, Import::Ordinal(raw.ordinal_or_hint)),
+                        _ => ImportEntry::new(raw.name, Import::Name(raw.export_name.unwrap_or(raw.name).to_owned())),
+                    };
+                    entry.kind = kind;
+                    entry.timestamp = Some(raw.timestamp);
+                    entry.header_name = Some(name.to_owned());
+                    if name_type != ImportNameType::Ordinal {
+                        entry.hint = Some(raw.ordinal_or_hint).filter(|&hint| hint != 0);
+                        // plain and EXPORTAS names are picked again when building
+                        entry.name_type = Some(name_type)
+                            .filter(|&name_type| name_type != ImportNameType::Name && name_type != ImportNameType::ExportAs);
+                    }
+
+                    let this = this.get_or_insert_with(|| {
+                        let builder = Self::new(raw.dll_name);
+                        match TargetArch::from_machine(raw.machine) {
+                            Some(arch) => builder.target_arch(arch),
+                            None => builder,
+                        }
+                    });
+                    if this.name != raw.dll_name {
+                        return Err(ParseError::MixedDlls(this.name.clone(), raw.dll_name.to_owned()));
+                    }
+                    this.imports.push(entry);
+
+                    let strings = raw.name.len() + raw.dll_name.len() + 2 + raw.export_name.map_or(0, |name| name.len() + 1);
+                    imports_end = offset + ARCHIVE_HEADER_LEN + SHORT_IMPORT_HEADER_LEN + strings;
+                }
+            }
+        }
+
+        let mut this = this.ok_or(ParseError::NoImports)?;
+        this.symbol_table = symbol_table;
+        this.long_names = long_names;
+        this.objects = passthrough;
+        this.null_import_descriptor = first.symbols.iter().any(|symbol| symbol == "__NULL_IMPORT_DESCRIPTOR");
+
+        while member_starts.iter().all(|start| start % (this.member_alignment * 2) == 0) {
+            this.member_alignment *= 2;
+        }
+        // only a lib ending right after its last import, off the alignment, left it unpadded.
+        // one that happens to end aligned is the same either way, so it keeps the default.
+        this.pad_final_member = bytes.len() != imports_end || bytes.len() % this.member_alignment == 0;
+        while this.pad_final_member && this.member_alignment > 2 && bytes.len() % this.member_alignment != 0 {
+            this.member_alignment /= 2;
+        }
+
+        // the lib wide settings are read back from the import descriptor
+        let dll_name = dll_file_name(&this.name);
+        if let Some((name, data)) = descriptor {
+            let characteristics = COFF_HEADER_LEN + COFF_SECTION_HEADER_LEN - 4;
+            this.timestamp = data.get(4..8).map_or(0, NativeEndian::read_u32);
+            this.kernel_mode = data.get(characteristics..characteristics + 4)
+                .map_or(false, |flags| NativeEndian::read_u32(flags) & IMAGE_SCN_MEM_NOT_PAGED != 0);
+
+            if let Some(member_name) = name.strip_suffix('/') {
+                if header_name(member_name) == name && name != header_name(&dll_name) {
+                    this.member_name = Some(member_name.to_owned());
+                }
+            }
+        }
+
+        // and only imports that differ from them keep their own
+        let timestamp = this.timestamp;
+        let default_name = header_name(this.member_name.as_ref().unwrap_or(&dll_name));
+        for entry in &mut this.imports {
+            entry.timestamp = entry.timestamp.filter(|&entry_timestamp| entry_timestamp != timestamp);
+            entry.header_name = entry.header_name.take().filter(|name| *name != default_name);
+        }
+
+        Ok(this)
+    }
+
+    /// Uses only the file name of `path`, so `target/debug/foo.dll` imports from `foo.dll`.
+    pub fn from_dll_path<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let file_name = path.file_name().unwrap_or(path.as_os_str());
+        Self::new(&file_name.to_string_lossy())
+    }
+
+    /// Clones the builder, importing the same symbols from a different DLL.
+    pub fn with_dll_name(&self, lib_name: &str) -> Self {
+        let mut this = self.clone();
+        this.name = lib_name.to_string();
+        this
+    }
+
+    /// Overrides the machine type, which otherwise defaults to the host arch.
+    pub fn target_arch(self, arch: TargetArch) -> Self {
+        let mut this = self;
+        this.arch = Some(arch);
+        this
+    }
+
+    /// Every import lib defines `__NULL_IMPORT_DESCRIPTOR`. Normally the linker only pulls
+    /// in one copy, but under `/WHOLEARCHIVE` each lib contributes its own and the link fails
+    /// with a duplicate symbol. Pass `false` for all but one of the libs in that case.
+    pub fn null_import_descriptor(self, emit: bool) -> Self {
+        let mut this = self;
+        this.null_import_descriptor = emit;
+        this
+    }
+
+    /// Pads every member to start on a multiple of `align`. Archives need at least 2.
+    pub fn member_alignment(self, align: usize) -> Self {
+        assert!(align >= 2 && align.is_power_of_two(), "member alignment must be a power of two >= 2");
+        let mut this = self;
+        this.member_alignment = align;
+        this
+    }
+
+    /// Selects the archive symbol index format, `ArSymbolTable::Msvc` by default. GNU `ar`
+    /// writes a single `/` member, which some non-MSVC tools expect.
+    pub fn symbol_table(self, symbol_table: ArSymbolTable) -> Self {
         let mut this = self;
-        this.imports.push((rust_name.to_owned(), Import::Name(lib_name.to_owned())));
+        this.symbol_table = symbol_table;
         this
     }
 
+    /// With `false`, the archive ends right after the last member's data, even when that
+    /// leaves it odd-sized, instead of padding it like every other member.
+    pub fn pad_final_member(self, pad: bool) -> Self {
+        let mut this = self;
+        this.pad_final_member = pad;
+        this
+    }
+
+    /// Marks the `.idata` sections of the descriptor members as non-pageable, as drivers
+    /// (`/DRIVER`) expect for their import tables. The import members themselves are the same
+    /// in both modes; thunks and `/GS` handling are generated by the linker, not the lib.
+    pub fn kernel_mode(self, kernel_mode: bool) -> Self {
+        let mut this = self;
+        this.kernel_mode = kernel_mode;
+        this
+    }
+
+    /// Sets the order of the import members, e.g. to match a reference lib. The descriptor and
+    /// object members always come first.
+    pub fn member_order(self, order: MemberOrder) -> Self {
+        let mut this = self;
+        this.member_order = order;
+        this
+    }
+
+    /// Sets the name in the archive header of every member, which defaults to the DLL name.
+    /// Names are cut to 15 bytes to fit the header.
+    pub fn member_name(self, name: &str) -> Self {
+        let mut this = self;
+        this.member_name = Some(name.to_owned());
+        this
+    }
+
+    /// Overrides the archive member name of the most recently added import.
+    pub fn import_member_name(self, name: &str) -> Self {
+        let mut this = self;
+        if let Some(entry) = this.imports.last_mut() {
+            entry.member_name = Some(name.to_owned());
+        }
+        this
+    }
+
+    /// Sets the TimeDateStamp of every generated member, which defaults to 0.
+    pub fn timestamp(self, timestamp: u32) -> Self {
+        let mut this = self;
+        this.timestamp = timestamp;
+        this
+    }
+
+    /// Skips the import descriptor, null descriptor and null thunk members, leaving the caller
+    /// to supply them with [`add_object`](Self::add_object).
+    pub fn manual_descriptors(self, manual: bool) -> Self {
+        let mut this = self;
+        this.manual_descriptors = manual;
+        this
+    }
+
+    /// Adds a prebuilt COFF object as a member, defining `symbols` in the linker members.
+    /// Objects are placed after the descriptors and before the imports.
+    pub fn add_object(self, data: Vec<u8>, symbols: &[&str]) -> Self {
+        let mut this = self;
+        this.objects.push(ObjectMember {
+            data,
+            symbols: symbols.iter().map(|s| s.to_string()).collect(),
+            header_name: None,
+        });
+        this
+    }
+
+    /// Adds a member defining `alias` as a weak external for `target`. `search` picks what the
+    /// linker does when nothing else defines `alias`.
+    pub fn weak_alias(self, alias: &str, target: &str, search: WeakSearch) -> Self {
+        let mut this = self;
+        this.weak_aliases.push((alias.to_owned(), target.to_owned(), search));
+        this
+    }
+
+    /// Adds a member holding `text` in an info-only `.comment` section, for tools reading the
+    /// lib. It defines no symbols, so linkers never pull it in.
+    pub fn add_metadata_comment(self, text: &str) -> Self {
+        let mut this = self;
+        this.comments.push(text.to_owned());
+        this
+    }
+
+    /// Imports the export `lib_name`, matched exactly, as the symbols `rust_name` and
+    /// `__imp_{rust_name}`. When the two names differ the member is an
+    /// `IMPORT_OBJECT_NAME_EXPORTAS`, which needs a linker that knows that name type (recent
+    /// `link.exe` and `lld`). No prefix is added or stripped on any arch, so a C runtime export
+    /// like `_strdup` is imported with its single leading underscore.
+    pub fn import_name(self, rust_name: &str, lib_name: &str) -> Self {
+        self.add_import(rust_name, Import::Name(lib_name.to_owned()))
+    }
+
     pub fn import_ordinal(self, rust_name: &str, lib_ordinal: u16) -> Self {
+        self.add_import(rust_name, Import::Ordinal(lib_ordinal))
+    }
+
+    /// Imports every ordinal in `range` as `Ordinal_{n}`, for DLLs where only the ordinals
+    /// are known. Ordinals start at 1, so building fails if the range includes 0.
+    pub fn import_all_ordinals(self, range: RangeInclusive<u16>) -> Self {
+        range.fold(self, |this, ordinal| this.import_ordinal(&format!("Ordinal_{}", ordinal), ordinal))
+    }
+
+    /// Adds an already constructed `Import`, for callers that computed or deserialized one.
+    pub fn add_import(self, rust_name: &str, import: Import) -> Self {
+        let mut this = self;
+        this.imports.push(ImportEntry::new(rust_name, import));
+        this
+    }
+
+    /// Imports one export both ways: by name as `rust_name`, and by ordinal as
+    /// `{rust_name}_by_ordinal`.
+    pub fn import_name_and_ordinal_both(self, rust_name: &str, lib_name: &str, lib_ordinal: u16) -> Self {
+        self.import_name(rust_name, lib_name)
+            .import_ordinal(&format!("{}_by_ordinal", rust_name), lib_ordinal)
+    }
+
+    pub fn import_names_with<I, F>(self, names: I, transform: F) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+        F: Fn(&str) -> String,
+    {
+        names.into_iter().fold(self, |this, name| {
+            let name = name.as_ref();
+            this.import_name(&transform(name), name)
+        })
+    }
+
+    /// Imports an export of a Rust DLL, such as a v0 mangled `_RNv...` symbol. This is
+    /// `import_name`: the export is matched exactly through an EXPORTAS member, so no arch
+    /// specific prefix or decoration is ever added or stripped.
+    pub fn import_rust(self, rust_name: &str, exported_symbol: &str) -> Self {
+        self.import_name(rust_name, exported_symbol)
+    }
+
+    /// Imports by name, with `hint` stored in the Hint field. The hint is an index into the
+    /// DLL's export name pointer table, not an ordinal: the loader tries the name at that index
+    /// first and otherwise searches the table. Lookup is by name only, there is no fallback to
+    /// an ordinal.
+    pub fn import_name_with_hint(self, rust_name: &str, lib_name: &str, hint: u16) -> Self {
+        let mut this = self.import_name(rust_name, lib_name);
+        if let Some(entry) = this.imports.last_mut() {
+            entry.hint = Some(hint);
+        }
+        this
+    }
+
+    /// Sets the kind of the most recently added import.
+    pub fn as_kind(self, kind: ImportKind) -> Self {
+        let mut this = self;
+        if let Some(entry) = this.imports.last_mut() {
+            entry.kind = kind;
+        }
+        this
+    }
+
+    /// Imports an export that the DLL forwards to another one, e.g. `B.func`. The import
+    /// member is an ordinary name import of this DLL, since the loader follows the forward;
+    /// `forward_target` is only recorded so it can be read back with `forward_target`.
+    pub fn import_forwarded(self, rust_name: &str, lib_name: &str, forward_target: &str) -> Self {
+        let mut this = self.import_name(rust_name, lib_name);
+        if let Some(entry) = this.imports.last_mut() {
+            entry.forward_target = Some(forward_target.to_owned());
+        }
+        this
+    }
+
+    /// Tags the most recently added import with a region label, e.g. `core` or `optional`.
+    /// Regions are only builder metadata for tooling; the lib is the same without them.
+    pub fn in_region(self, region: &str) -> Self {
+        let mut this = self;
+        if let Some(entry) = this.imports.last_mut() {
+            entry.region = Some(region.to_owned());
+        }
+        this
+    }
+
+    /// The imports tagged with `region`, in the order they were added.
+    pub fn imports_in_region(&self, region: &str) -> Vec<(&str, &Import)> {
+        self.imports.iter()
+            .filter(|entry| entry.region.as_deref() == Some(region))
+            .map(|entry| (entry.rust_name.as_str(), &entry.import))
+            .collect()
+    }
+
+    /// The forward target recorded by `import_forwarded` for `rust_name`, if any.
+    pub fn forward_target(&self, rust_name: &str) -> Option<&str> {
+        self.imports.iter()
+            .find(|entry| entry.rust_name == rust_name)
+            .and_then(|entry| entry.forward_target.as_deref())
+    }
+
+    /// Overrides the TimeDateStamp of the most recently added import.
+    pub fn import_timestamp(self, timestamp: u32) -> Self {
         let mut this = self;
-        this.imports.push((rust_name.to_owned(), Import::Ordinal(lib_ordinal)));
+        if let Some(entry) = this.imports.last_mut() {
+            entry.timestamp = Some(timestamp);
+        }
         this
     }
 
+    pub fn as_code(self) -> Self {
+        self.as_kind(ImportKind::Code)
+    }
+
+    pub fn as_data(self) -> Self {
+        self.as_kind(ImportKind::Data)
+    }
+
+    pub fn as_const(self) -> Self {
+        self.as_kind(ImportKind::Const)
+    }
+
+    /// The symbols listed in the linker members, in archive order.
+    pub fn exported_symbols(&self) -> Vec<String> {
+        let archive_builder = self.coff_archive().unwrap();
+        archive_builder.symbols.into_iter().map(|(name, _)| name).collect()
+    }
+
+    /// Removes imports whose rust name was already imported, keeping the first, then sorts the
+    /// rest by rust name. Ordinal 0 imports are reported but kept, and fail to build.
+    pub fn normalize(&mut self) -> NormalizeReport {
+        let mut report = NormalizeReport::default();
+        let mut seen = HashSet::new();
+
+        let imports = std::mem::take(&mut self.imports);
+        for entry in imports {
+            if seen.insert(entry.rust_name.clone()) {
+                self.imports.push(entry);
+            } else {
+                report.removed.push((entry.rust_name, entry.import));
+            }
+        }
+
+        self.imports.sort_by(|a, b| a.rust_name.cmp(&b.rust_name));
+
+        report.invalid_ordinals = self.imports.iter()
+            .filter(|entry| entry.import == Import::Ordinal(0))
+            .map(|entry| entry.rust_name.clone())
+            .collect();
+
+        report
+    }
+
+    /// Number of symbols in the linker members: one per descriptor member,
+    /// plus `__imp_` and thunk symbols for each import (data imports have no thunk).
+    /// Imports sharing a rust name fail to build, so they are counted as they were added.
+    pub fn total_symbol_count(&self) -> usize {
+        let descriptors = match (self.manual_descriptors, self.null_import_descriptor) {
+            (true, _) => 0,
+            (false, true) => 3,
+            (false, false) => 2,
+        };
+        let objects = self.objects.iter().map(|object| object.symbols.len()).sum::<usize>()
+            + self.weak_aliases.len();
+        let imports = self.imports.iter()
+            .map(|entry| if entry.kind == ImportKind::Data { 1 } else { 2 })
+            .sum::<usize>();
+        descriptors + objects + imports
+    }
+
     pub fn build(self) -> Vec<u8> {
-        build_library(self).unwrap()
+        self.try_build().unwrap()
     }
-}
 
-const IMAGE_SYM_CLASS_EXTERNAL: u8 = 0;
-const IMAGE_SYM_CLASS_SECTION: u8 = 0;
-const IMAGE_SYM_CLASS_STATIC: u8 = 0;
+    pub fn try_build(self) -> io::Result<Vec<u8>> {
+        #[cfg(feature = "debug_verify")]
+        let builder = self.clone();
+
+        let lib = build_library(self)?;
+
+        #[cfg(feature = "debug_verify")]
+        {
+            if let Err(e) = verify::verify(&builder, &lib) {
+                panic!("built an inconsistent import lib: {}", e);
+            }
+        }
+
+        Ok(lib)
+    }
+
+    /// For build scripts: writes the lib to `out_dir/{lib_stem}.lib` and prints the
+    /// `cargo:rustc-link-search` and `cargo:rustc-link-lib` lines that link against it.
+    #[cfg(feature = "build-script")]
+    pub fn build_and_emit_cargo_directives(self, out_dir: &Path, lib_stem: &str) -> io::Result<()> {
+        self.emit_cargo_directives(out_dir, lib_stem, &mut io::stdout())
+    }
+
+    #[cfg(feature = "build-script")]
+    fn emit_cargo_directives<W: Write>(self, out_dir: &Path, lib_stem: &str, out: &mut W) -> io::Result<()> {
+        let lib = self.try_build()?;
+        std::fs::write(out_dir.join(format!("{}.lib", lib_stem)), lib)?;
+
+        writeln!(out, "cargo:rustc-link-search=native={}", out_dir.display())?;
+        writeln!(out, "cargo:rustc-link-lib=dylib={}", lib_stem)
+    }
+
+    /// Writes the lib in a single pass, seeking back to fill in the linker member offsets.
+    /// Produces the same bytes as [`build`](Self::build).
+    ///
+    /// With `debug_verify` the lib is built in memory and checked first, like `try_build`.
+    pub fn write_to_seek<W: Write + Seek>(self, w: &mut W) -> io::Result<()> {
+        if cfg!(feature = "debug_verify") {
+            let lib = self.try_build()?;
+            return w.write_all(&lib);
+        }
+        write_library(self, w)
+    }
+
+    /// Flags name imports that look decorated while being matched against the export as is,
+    /// which tends to end in an unresolved external at link or load time.
+    pub fn lint(&self) -> Vec<LintWarning> {
+        let mut warnings = vec![];
+        for entry in &self.imports {
+            let name = match &entry.import {
+                Import::Name(name) => name,
+                Import::Ordinal(_) => continue,
+            };
+            if entry.name_type.unwrap_or(ImportNameType::Name) != ImportNameType::Name {
+                continue;
+            }
+
+            let (rust_name, name) = (entry.rust_name.clone(), name.clone());
+            if name.starts_with('?') {
+                warnings.push(LintWarning::CppMangledName { rust_name, name });
+            } else if name.contains('@') {
+                warnings.push(LintWarning::DecoratedName { rust_name, name });
+            }
+        }
+
+        warnings
+    }
+
+    // an import sharing a descriptor's symbol would silently take over its index entry
+    fn check_descriptor_symbols(&self) -> io::Result<()> {
+        if self.manual_descriptors {
+            return Ok(());
+        }
+
+        let mut descriptors = CoffArchiveBuilder::new(&self.name, self.arch.unwrap_or_else(TargetArch::host));
+        descriptors.add_import_descriptors(self.null_import_descriptor)?;
+        for entry in &self.imports {
+            let imp = format!("__imp_{}", entry.rust_name);
+            if let Some(symbol) = [&entry.rust_name, &imp].iter().find(|s| descriptors.symbols.contains_key(s.as_str())) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("`{}` collides with the import descriptor symbol `{}`", entry.rust_name, symbol)));
+            }
+        }
+
+        Ok(())
+    }
+
+    // two members defining the same `__imp_` symbol would leave one unreachable
+    fn check_rust_names(&self) -> io::Result<()> {
+        let mut seen = HashSet::new();
+        for entry in &self.imports {
+            if !seen.insert(&entry.rust_name) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                    format!("`{}` is imported more than once from {}", entry.rust_name, self.name)));
+            }
+        }
+
+        Ok(())
+    }
+
+    // ordinals start at 1, and two different imports claiming one ordinal of the same DLL
+    // can't both be right
+    fn check_ordinals(&self) -> io::Result<()> {
+        let mut seen = HashMap::new();
+        for entry in &self.imports {
+            if let Some(ordinal) = entry.import.ordinal() {
+                if ordinal == 0 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                        format!("`{}` imports ordinal 0 of {}, ordinals start at 1", entry.rust_name, self.name)));
+                }
+                if let Some(first) = seen.insert(ordinal, &entry.rust_name) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                        format!("`{}` and `{}` both import ordinal {} of {}", first, entry.rust_name, ordinal, self.name)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Breaks the size of the built lib down by member. Duplicate ordinals are only rejected
+    /// when building, so they don't affect the report; other errors `build` would hit panic.
+    pub fn size_report(&self) -> SizeReport {
+        let archive_builder = self.coff_archive().unwrap();
+        let symbols: Vec<_> = archive_builder.symbols.into_iter().collect();
+        let (linker_count, member_starts) = library_layout(self, &symbols, &archive_builder.sections).unwrap();
+
+        let mut slots = member_starts.windows(2).map(|w| w[1] - w[0]);
+        let mut next = |present: bool| if present { slots.next().unwrap_or(0) } else { 0 };
+
+        let descriptors = !self.manual_descriptors;
+        let mut report = SizeReport {
+            signature: ARCHIVE_SIG.len(),
+            first_linker_member: next(true),
+            second_linker_member: next(linker_count == 2),
+            objects: next(self.long_names.is_some()),
+            import_descriptor: next(descriptors),
+            null_import_descriptor: next(descriptors && self.null_import_descriptor),
+            null_thunk_data: next(descriptors),
+            ..SizeReport::default()
+        };
+        let objects = self.objects.len() + self.comments.len() + self.weak_aliases.len();
+        report.objects += (0..objects).map(|_| next(true)).sum::<usize>();
+        report.short_imports = slots.sum();
+
+        report
+    }
+
+    // the imports in the order their members are emitted
+    fn ordered_imports(&self) -> Vec<&ImportEntry> {
+        let mut imports: Vec<_> = self.imports.iter().collect();
+        match self.member_order {
+            MemberOrder::Insertion => {}
+            MemberOrder::ByName => imports.sort_by(|a, b| a.rust_name.cmp(&b.rust_name)),
+            MemberOrder::ByOrdinal => imports.sort_by_key(|entry| (entry.import.ordinal().is_none(), entry.import.ordinal())),
+            MemberOrder::Custom(cmp) => imports.sort_by(|a, b| {
+                cmp((&a.rust_name, &a.import), (&b.rust_name, &b.import))
+            }),
+        }
+        imports
+    }
+
+    fn coff_archive(&self) -> io::Result<CoffArchiveBuilder> {
+        let arch = self.arch.unwrap_or_else(TargetArch::host);
+        let mut archive_builder = CoffArchiveBuilder::new(&self.name, arch);
+        archive_builder.kernel_mode = self.kernel_mode;
+        archive_builder.timestamp = self.timestamp;
+        archive_builder.member_name = self.member_name.clone();
+
+        if let Some(long_names) = &self.long_names {
+            archive_builder.add_long_names(long_names);
+        }
+
+        if !self.manual_descriptors {
+            archive_builder.add_import_descriptors(self.null_import_descriptor)?;
+        }
+
+        for object in &self.objects {
+            archive_builder.add_object(object);
+        }
+
+        for comment in &self.comments {
+            archive_builder.add_comment(comment)?;
+        }
+
+        for (alias, target, search) in &self.weak_aliases {
+            archive_builder.add_weak_alias(alias, target, *search)?;
+        }
+
+        for entry in self.ordered_imports() {
+            archive_builder.add_short_import(entry)?;
+        }
+
+        Ok(archive_builder)
+    }
+}
 
+pub const IMAGE_SYM_CLASS_EXTERNAL: u8 = 2;
+pub const IMAGE_SYM_CLASS_STATIC: u8 = 3;
+pub const IMAGE_SYM_CLASS_LABEL: u8 = 6;
+pub const IMAGE_SYM_CLASS_FUNCTION: u8 = 101;
+pub const IMAGE_SYM_CLASS_SECTION: u8 = 104;
+pub const IMAGE_SYM_CLASS_WEAK_EXTERNAL: u8 = 105;
+
+const IMAGE_SCN_CNT_INITIALIZED_DATA: u32 = 0x0000_0040;
+const IMAGE_SCN_LNK_INFO: u32 = 0x0000_0200;
+const IMAGE_SCN_LNK_REMOVE: u32 = 0x0000_0800;
+const IMAGE_SCN_ALIGN_1BYTES: u32 = 0x0010_0000;
+const IMAGE_SCN_ALIGN_2BYTES: u32 = 0x0020_0000;
+const IMAGE_SCN_ALIGN_4BYTES: u32 = 0x0030_0000;
+const IMAGE_SCN_ALIGN_8BYTES: u32 = 0x0040_0000;
+const IMAGE_SCN_MEM_NOT_PAGED: u32 = 0x0800_0000;
+const IMAGE_SCN_MEM_READ: u32 = 0x4000_0000;
+const IMAGE_SCN_MEM_WRITE: u32 = 0x8000_0000;
+
+/// Size of the `IMPORT_OBJECT_HEADER` that starts every short import member, before the
+/// import name and DLL name strings.
+pub const SHORT_IMPORT_HEADER_LEN: usize = 20;
+
+// an IMAGE_IMPORT_DESCRIPTOR, the contents of .idata$2 and .idata$3
+const IMPORT_DESCRIPTOR_LEN: usize = 0x14;
 const COFF_HEADER_LEN: usize = 0x14;
-const COFF_SECTION_HEADER_LEN: usize = 0x12;
-const ARCHIVE_HEADER_LEN: usize = 0x3C;
-const ARCHIVE_SIG: &[u8] = b"!<arch>\n";
+const COFF_SECTION_HEADER_LEN: usize = 0x28;
+pub(crate) const ARCHIVE_HEADER_LEN: usize = 0x3C;
+pub(crate) const ARCHIVE_SIG: &[u8] = b"!<arch>\n";
 
 
 fn build_library(imports: ImportLibBuilder) -> io::Result<Vec<u8>> {
+    imports.check_rust_names()?;
+    imports.check_ordinals()?;
+    imports.check_descriptor_symbols()?;
+    let archive_builder = imports.coff_archive()?;
+
+    let members = archive_builder.sections.len();
+    let member_names = archive_builder.member_names();
+    let symbols: Vec<_> = archive_builder.symbols.into_iter().collect();
+
+    let (linker_count, member_starts) = library_layout(&imports, &symbols, &archive_builder.sections)?;
+    let offsets = &member_starts[linker_count..linker_count + members];
+
     let mut import_lib = ARCHIVE_SIG.to_vec();
-    let mut archive_builder = CoffArchiveBuilder::new(&imports.name);
+    let linker_data = linker_members(imports.symbol_table, &symbols, offsets)?;
+    let all_members = linker_data.iter().map(|data| ("/", data))
+        .chain(member_names.iter().map(String::as_str).zip(&archive_builder.sections));
 
-    archive_builder.add_import_descriptors()?;
+    for (i, (name, data)) in all_members.enumerate() {
+        write_member(&mut import_lib, name, data, member_starts[i + 1] - member_starts[i])?;
+    }
 
+    Ok(import_lib)
+}
 
-    for (name, import) in &imports.imports {
-        archive_builder.add_short_import(name, import)?;
+// number of linker members, and the start of every member (linker members first) followed by
+// the end of the archive
+fn library_layout(imports: &ImportLibBuilder, symbols: &[(String, usize)], sections: &[Vec<u8>]) -> io::Result<(usize, Vec<usize>)> {
+    // the linker members' size doesn't depend on the offsets they hold
+    let placeholder = vec![0; sections.len()];
+    let linker_sizes = linker_members(imports.symbol_table, symbols, &placeholder)?;
+
+    let member_sizes: Vec<_> = linker_sizes.iter().map(|d| d.len())
+        .chain(sections.iter().map(|d| d.len()))
+        .collect();
+    let mut member_starts = member_offsets(ARCHIVE_SIG.len(), member_sizes.iter().copied(), imports.member_alignment);
+    if !imports.pad_final_member {
+        let last = member_sizes.len();
+        member_starts[last] = member_starts[last - 1] + ARCHIVE_HEADER_LEN + member_sizes[last - 1];
     }
 
-    println!("{:?}", archive_builder.symbols);
+    Ok((linker_sizes.len(), member_starts))
+}
+
+// single pass version of `build_library`: the linker members are written with placeholder
+// offsets, then rewritten once the member positions are known.
+fn write_library<W: Write + Seek>(imports: ImportLibBuilder, w: &mut W) -> io::Result<()> {
+    imports.check_rust_names()?;
+    imports.check_ordinals()?;
+    imports.check_descriptor_symbols()?;
+    let archive_builder = imports.coff_archive()?;
 
     let members = archive_builder.sections.len();
+    let member_names = archive_builder.member_names();
     let symbols: Vec<_> = archive_builder.symbols.into_iter().collect();
 
-    let symbol_table_len = symbols.iter().map(|s| s.0.len() + 1).sum::<usize>();
+    let archive_start = w.stream_position()?;
+    w.write_all(ARCHIVE_SIG)?;
 
-    let first_linker_len = 4 + 4 * symbols.len() + symbol_table_len;
-    let second_linker_len = 8 + 4 * members + 2 * symbols.len() + symbol_table_len;
+    let placeholder = vec![0; members];
+    let linker_data = linker_members(imports.symbol_table, &symbols, &placeholder)?;
+    let linker_count = linker_data.len();
+    let all_members = linker_data.iter().map(|data| ("/", data))
+        .chain(member_names.iter().map(String::as_str).zip(&archive_builder.sections));
 
-    let mut import_start = ARCHIVE_SIG.len();
-    import_start += ARCHIVE_HEADER_LEN + first_linker_len;
-    if import_start % 2 != 0 {
-        import_start += 1;
-    }
-    import_start += ARCHIVE_HEADER_LEN + second_linker_len;
-    if import_start % 2 != 0 {
-        import_start += 1;
+    let mut member_starts = vec![];
+    for (i, (name, data)) in all_members.enumerate() {
+        let start = (w.stream_position()? - archive_start) as usize;
+        let end = if i == linker_count + members - 1 && !imports.pad_final_member {
+            start + ARCHIVE_HEADER_LEN + data.len()
+        } else {
+            align_to(start + ARCHIVE_HEADER_LEN + data.len(), imports.member_alignment)
+        };
+        member_starts.push(start);
+        write_member(w, name, data, end - start)?;
     }
 
-    let mut offsets = vec![];
-    for d in &archive_builder.sections {
-        offsets.push(import_start);
-        import_start += ARCHIVE_HEADER_LEN + d.len();
-        if import_start % 2 != 0 {
-            import_start += 1;
-        }
+    let archive_end = w.stream_position()?;
+    let offsets = &member_starts[linker_count..];
+
+    for (start, data) in member_starts.iter().zip(linker_members(imports.symbol_table, &symbols, offsets)?) {
+        w.seek(SeekFrom::Start(archive_start + (start + ARCHIVE_HEADER_LEN) as u64))?;
+        w.write_all(&data)?;
     }
+    w.seek(SeekFrom::Start(archive_end))?;
 
-    println!("{:?}, {:?}", offsets, symbols);
-    write_header(&mut import_lib, "", first_linker_len)?;
-    import_lib.write_u32::<BigEndian>(symbols.len() as u32)?; // number of symbols
+    Ok(())
+}
 
-    for (_name, i) in &symbols {
-        let offset = offsets[i-1];
-        import_lib.write_u32::<BigEndian>(offset as u32)?;
+fn linker_members(symbol_table: ArSymbolTable, symbols: &[(String, usize)], offsets: &[usize]) -> io::Result<Vec<Vec<u8>>> {
+    match symbol_table {
+        ArSymbolTable::Msvc => Ok(vec![first_linker_member(symbols, offsets)?, second_linker_member(symbols, offsets)?]),
+        // GNU ar's `/` member has the same layout as the first linker member
+        ArSymbolTable::Gnu => Ok(vec![first_linker_member(symbols, offsets)?]),
     }
+}
 
-    for symbol in &symbols {
-        import_lib.write_all(symbol.0.as_bytes())?;
-        import_lib.write_u8(b'\0')?;
+// symbols are paired with the 1-based index of the member that defines them
+fn first_linker_member(symbols: &[(String, usize)], offsets: &[usize]) -> io::Result<Vec<u8>> {
+    let mut first_linker = vec![];
+    first_linker.write_u32::<BigEndian>(symbols.len() as u32)?; // number of symbols
+
+    for (_name, i) in symbols {
+        first_linker.write_u32::<BigEndian>(symbol_offset(offsets[i-1])?)?;
     }
 
-    if import_lib.len() % 2 != 0 {
-        import_lib.write_u8(b'\0')?;
+    for symbol in symbols {
+        first_linker.write_all(symbol.0.as_bytes())?;
+        first_linker.write_u8(b'\0')?;
     }
 
-    let mut symbols = symbols;
-    symbols.sort_by_key(|c| c.1);
+    Ok(first_linker)
+}
+
+// unlike the first member, this one is sorted by name (the linker binary searches it) and refers
+// to members by their 1-based index into the offset table rather than by offset
+fn second_linker_member(symbols: &[(String, usize)], offsets: &[usize]) -> io::Result<Vec<u8>> {
+    let mut symbols = symbols.to_vec();
+    symbols.sort_by(|a, b| a.0.cmp(&b.0));
 
-    write_header(&mut import_lib, "", second_linker_len)?;
-    import_lib.write_u32::<NativeEndian>(members as u32)?;
+    let mut second_linker = vec![];
+    second_linker.write_u32::<NativeEndian>(offsets.len() as u32)?;
 
     for offset in offsets {
-        import_lib.write_u32::<NativeEndian>(offset as u32)?;
+        second_linker.write_u32::<NativeEndian>(symbol_offset(*offset)?)?;
     }
-    
-    import_lib.write_u32::<NativeEndian>(symbols.len() as u32)?;
-    for (_symbol, offset) in &symbols {
-        import_lib.write_u16::<NativeEndian>(*offset as u16)?;
+
+    second_linker.write_u32::<NativeEndian>(symbols.len() as u32)?;
+    for (_symbol, index) in &symbols {
+        let index = u16::try_from(*index).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput,
+            format!("member index {} does not fit in the second linker member", index)))?;
+        second_linker.write_u16::<NativeEndian>(index)?;
     }
 
     for symbol in &symbols {
-        import_lib.write_all(symbol.0.as_bytes())?;
-        import_lib.write_u8(b'\0')?;
+        second_linker.write_all(symbol.0.as_bytes())?;
+        second_linker.write_u8(b'\0')?;
     }
 
-    if import_lib.len() % 2 != 0 {
-        import_lib.write_u8(b'\0')?;
-    }
+    Ok(second_linker)
+}
 
-    for data in archive_builder.sections {
-        write_header(&mut import_lib, &imports.name, data.len())?;
-        import_lib.write_all(&data)?;
-        if import_lib.len() % 2 != 0 {
-            import_lib.write_u8(b'\0')?;
-        }
+// the symbol index only has 32-bit offsets, so members can't start past 4 GiB
+fn symbol_offset(offset: usize) -> io::Result<u32> {
+    u32::try_from(offset).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput,
+        format!("member offset {:#x} does not fit in the archive symbol index", offset)))
+}
+
+// rounds `n` up to a multiple of `align`
+fn align_to(n: usize, align: usize) -> usize {
+    (n + align - 1) / align * align
+}
+
+// start of each member given the size of its data, followed by the end of the archive
+fn member_offsets<I: IntoIterator<Item = usize>>(start: usize, sizes: I, align: usize) -> Vec<usize> {
+    let mut offsets = vec![start];
+    let mut start = start;
+    for size in sizes {
+        start = align_to(start + ARCHIVE_HEADER_LEN + size, align);
+        offsets.push(start);
     }
+    offsets
+}
 
-    Ok(import_lib)
+// writes a member filling `slot_len` bytes. anything beyond the usual even-byte pad is counted
+// in the member size, so readers walking the archive land on the next header.
+fn write_member<W: Write>(buf: &mut W, name: &str, data: &[u8], slot_len: usize) -> io::Result<()> {
+    let padded_len = slot_len - ARCHIVE_HEADER_LEN;
+    let size = if padded_len - data.len() <= 1 {
+        data.len()
+    } else {
+        padded_len
+    };
+
+    write_header(buf, name, size)?;
+    buf.write_all(data)?;
+    buf.write_all(&vec![0; padded_len - data.len()])
+}
+
+// the name field of a regular member: the name cut to at most 15 bytes on a char boundary,
+// ending in `/`
+fn header_name(name: &str) -> String {
+    let mut end = name.len().min(15);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}/", &name[..end])
 }
 
 fn write_header<W: Write>(buf: &mut W, name: &str, len: usize) -> io::Result<()> {
-    let name = format!("{}/", if name.len() > 15 {
-        &name[0..15]
-    } else {
-        &name
-    });
-
-    write!(buf, "{:<16}", name)?;
-    write!(buf, "{:<12}", -1)?; // Date (-1 in windows tools)
-    write!(buf, "      ")?; // user id (all blanks)
-    write!(buf, "      ")?; // group id (all blanks)
-    write!(buf, "{:<8}", 0)?; // mode
-    write!(buf, "{:<10}`\n", len)?; // size and end
-    Ok(())
+    let mut header = Vec::with_capacity(ARCHIVE_HEADER_LEN);
+    // padded by bytes, `{:<16}` would count chars and come up short for non-ascii names
+    header.extend_from_slice(name.as_bytes());
+    header.resize(header.len().max(16), b' ');
+    write!(header, "{:<12}", -1)?; // Date (-1 in windows tools)
+    write!(header, "      ")?; // user id (all blanks)
+    write!(header, "      ")?; // group id (all blanks)
+    write!(header, "{:<8}", 0)?; // mode
+    writeln!(header, "{:<10}`", len)?; // size and end
+
+    // every offset assumes fixed size headers, so a name or size overflowing its field is fatal
+    if header.len() != ARCHIVE_HEADER_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("member `{}` of size {} does not fit in an archive header", name, len)));
+    }
+
+    buf.write_all(&header)
 }
 
-fn arch() -> u16 {
-    if cfg!(target_arch = "x86_64") {
-        0x8664
-    } else if cfg!(target_arch = "x86") {
-        0x014C
+// symbols of the members `add_import_descriptors` generates
+fn is_descriptor_symbol(symbol: &str) -> bool {
+    symbol.starts_with("__IMPORT_DESCRIPTOR_")
+        || symbol == "__NULL_IMPORT_DESCRIPTOR"
+        || (symbol.starts_with('\x7F') && symbol.ends_with("_NULL_THUNK_DATA"))
+}
+
+// the loader needs a real file name, so `kernel32` is embedded as `kernel32.dll`
+fn dll_file_name(name: &str) -> String {
+    if Path::new(name).extension().is_some() {
+        name.to_owned()
     } else {
-        panic!("unsupported arch")
+        format!("{}.dll", name)
     }
 }
 
+// descriptor symbols use the bare name: `__IMPORT_DESCRIPTOR_kernel32`
+fn dll_stem(name: &str) -> &str {
+    name.strip_suffix(".dll").unwrap_or(name)
+}
+
 #[derive(Debug)]
 struct CoffArchiveBuilder {
     symbols: IndexMap<String, usize>,
     sections: Vec<Vec<u8>>,
     archive_name: String,
+    // archive member name, defaulting to the dll name, and overrides by 1-based member index
+    member_name: Option<String>,
+    member_names: HashMap<usize, String>,
+    arch: TargetArch,
+    kernel_mode: bool,
+    timestamp: u32,
 }
 
 impl CoffArchiveBuilder {
-    fn new(name: &str) -> Self {
+    fn new(name: &str, arch: TargetArch) -> Self {
         Self {
             symbols: IndexMap::new(),
             sections: vec![],
-            archive_name: name.to_owned(),
+            archive_name: dll_file_name(name),
+            member_name: None,
+            member_names: HashMap::new(),
+            arch,
+            kernel_mode: false,
+            timestamp: 0,
         }
     }
 
-    fn add_import_descriptors(&mut self) -> io::Result<()> {
-        let (name, data) = build_import_descriptor(&self.archive_name)?;
+    // the header name field of every member
+    fn member_names(&self) -> Vec<String> {
+        let default = header_name(self.member_name.as_ref().unwrap_or(&self.archive_name));
+        (1..=self.sections.len())
+            .map(|i| self.member_names.get(&i).unwrap_or(&default).clone())
+            .collect()
+    }
+
+    fn add_import_descriptors(&mut self, null_import_descriptor: bool) -> io::Result<()> {
+        let (name, data) = build_import_descriptor(&self.archive_name, self.arch, self.kernel_mode, self.timestamp)?;
 
         self.sections.push(data);
         self.symbols.insert(name, self.sections.len());
 
-        let (name, data) = build_null_import_descriptor()?;
+        if null_import_descriptor {
+            let (name, data) = build_null_import_descriptor(self.arch, self.kernel_mode, self.timestamp)?;
 
-        self.sections.push(data);
-        self.symbols.insert(name, self.sections.len());
+            self.sections.push(data);
+            self.symbols.insert(name, self.sections.len());
+        }
 
-        let (name, data) = build_null_thunk_data(&self.archive_name)?;
+        let (name, data) = build_null_thunk_data(&self.archive_name, self.arch, self.kernel_mode, self.timestamp)?;
 
         self.sections.push(data);
         self.symbols.insert(name, self.sections.len());
@@ -220,50 +1381,102 @@ impl CoffArchiveBuilder {
         Ok(())
     }
 
-    fn add_short_import(&mut self, rust_name: &str, import: &Import) -> io::Result<()> {
+    fn add_long_names(&mut self, data: &[u8]) {
+        self.sections.push(data.to_vec());
+        self.member_names.insert(self.sections.len(), "//".to_owned());
+    }
+
+    fn add_object(&mut self, object: &ObjectMember) {
+        self.sections.push(object.data.clone());
+        for symbol in &object.symbols {
+            self.symbols.insert(symbol.clone(), self.sections.len());
+        }
+        if let Some(header_name) = &object.header_name {
+            self.member_names.insert(self.sections.len(), header_name.clone());
+        }
+    }
+
+    fn add_comment(&mut self, text: &str) -> io::Result<()> {
+        self.sections.push(build_comment_object(text, self.arch, self.timestamp)?);
+        Ok(())
+    }
+
+    fn add_weak_alias(&mut self, alias: &str, target: &str, search: WeakSearch) -> io::Result<()> {
+        self.sections.push(build_weak_external_object(alias, target, search, self.arch, self.timestamp)?);
+        self.symbols.insert(alias.to_owned(), self.sections.len());
+        Ok(())
+    }
+
+    fn add_short_import(&mut self, entry: &ImportEntry) -> io::Result<()> {
+        let import = &entry.import;
         let mut short_import = vec![];
         short_import.write_u16::<NativeEndian>(0x0000)?; // IMAGE_FILE_MACHINE_UNKNOWN
         short_import.write_u16::<NativeEndian>(0xFFFF)?; // Reserved
         short_import.write_u16::<NativeEndian>(0x0)?;    // Version
-        let arch:u16 = arch();
-        short_import.write_u16::<NativeEndian>(arch)?;   // Arch
-        short_import.write_u32::<NativeEndian>(0x0)?;    // Time/Date (todo: actaul value)
+        short_import.write_u16::<NativeEndian>(self.arch.machine())?; // Arch
+        short_import.write_u32::<NativeEndian>(entry.timestamp.unwrap_or(self.timestamp))?; // Time/Date
 
-        let item_name = import.name().unwrap_or_default();
+        // the linker defines `__imp_{name}` and `{name}` from the name string, so it is always
+        // the rust name, even when the export is by ordinal
+        let item_name = entry.rust_name.as_str();
         let dll_name = self.archive_name.as_str();
 
-        let size = dll_name.len() + item_name.len() + 2;
+        let name_type = entry.name_type.unwrap_or(if import.ordinal().is_some() {
+            ImportNameType::Ordinal
+        } else {
+            ImportNameType::Name
+        });
+        // an export with a different name than the rust one follows the DLL name
+        let export_name = import.name()
+            .filter(|&name| name_type == ImportNameType::Name && name != entry.rust_name);
+        let name_type = if export_name.is_some() { ImportNameType::ExportAs } else { name_type };
+
+        // SizeOfData: the name strings and their NULs that follow the header.
+        // it never describes the imported object, not even for data imports.
+        let size = dll_name.len() + item_name.len() + 2 + export_name.map_or(0, |name| name.len() + 1);
         short_import.write_u32::<NativeEndian>(size as u32)?;
-        let ordinal = import.ordinal().unwrap_or_default();
+        let ordinal = import.ordinal().or(entry.hint).unwrap_or_default();
         short_import.write_u16::<NativeEndian>(ordinal)?;
 
-        let import_type = 0x00; // IMPORT_CODE
-        let import_name_type: u16 = if import.ordinal().is_some() {
-            0x0 // IMPORT_ORDINAL
-        } else {
-            0x1 // IMPORT_NAME
-        };
-        short_import.write_u16::<NativeEndian>(import_type + (import_name_type << 2))?;
+        short_import.write_u16::<NativeEndian>(import_object_type(entry.kind, name_type))?;
+        debug_assert_eq!(short_import.len(), SHORT_IMPORT_HEADER_LEN);
+
         short_import.write_all(item_name.as_bytes())?;
         short_import.write_u8(b'\0')?;
         short_import.write_all(dll_name.as_bytes())?;
         short_import.write_u8(b'\0')?;
-
+        if let Some(export_name) = export_name {
+            short_import.write_all(export_name.as_bytes())?;
+            short_import.write_u8(b'\0')?;
+        }
 
         self.sections.push(short_import);
-        self.symbols.insert(format!("__imp_{}", rust_name), self.sections.len());
-        self.symbols.insert(rust_name.to_string(), self.sections.len());
+        self.symbols.insert(format!("__imp_{}", entry.rust_name), self.sections.len());
+        if let Some(header_name) = &entry.header_name {
+            self.member_names.insert(self.sections.len(), header_name.clone());
+        } else if let Some(member_name) = &entry.member_name {
+            self.member_names.insert(self.sections.len(), header_name(member_name));
+        }
+        // data has no thunk, so it can only be reached through `__imp_`
+        if entry.kind != ImportKind::Data {
+            self.symbols.insert(entry.rust_name.clone(), self.sections.len());
+        }
 
         Ok(())
     }
 }
 
-fn build_import_descriptor(archive_name: &str) -> io::Result<(String, Vec<u8>)> {
-    let name = if archive_name.ends_with(".dll") {
-        &archive_name[.. archive_name.len() - 4]
+fn idata_characteristics(align: u32, kernel_mode: bool) -> u32 {
+    let characteristics = IMAGE_SCN_CNT_INITIALIZED_DATA | align | IMAGE_SCN_MEM_READ | IMAGE_SCN_MEM_WRITE;
+    if kernel_mode {
+        characteristics | IMAGE_SCN_MEM_NOT_PAGED
     } else {
-        &archive_name
-    };
+        characteristics
+    }
+}
+
+fn build_import_descriptor(archive_name: &str, arch: TargetArch, kernel_mode: bool, timestamp: u32) -> io::Result<(String, Vec<u8>)> {
+    let name = dll_stem(archive_name);
 
     let import_desc_name = format!("__IMPORT_DESCRIPTOR_{}", name);
     let null_import_data = "__NULL_IMPORT_DESCRIPTOR".to_owned();
@@ -275,34 +1488,34 @@ fn build_import_descriptor(archive_name: &str) -> io::Result<(String, Vec<u8>)>
     const N_RECLOCATIONS: u16 = 3;
 
     let mut buffer = vec![];
-    buffer.write_u16::<NativeEndian>(arch())?;
+    buffer.write_u16::<NativeEndian>(arch.machine())?;
     buffer.write_u16::<NativeEndian>(N_SECTIONS)?;
-    buffer.write_u32::<NativeEndian>(0)?; // TIMESTAMP
+    buffer.write_u32::<NativeEndian>(timestamp)?; // TIMESTAMP
 
     let data_len = COFF_HEADER_LEN + N_SECTIONS as usize * COFF_SECTION_HEADER_LEN +
         // .idata$2
-        20 + N_RECLOCATIONS as usize * 10 +
+        IMPORT_DESCRIPTOR_LEN + N_RECLOCATIONS as usize * 10 +
         // .idata$6
         archive_name.len() + 1;
     
     buffer.write_u32::<NativeEndian>(data_len as u32)?; // aka: symbol table start
     buffer.write_u32::<NativeEndian>(N_SYMBOLS)?;
-    buffer.write_u16::<NativeEndian>(0)?; // always 0 for libs
-    buffer.write_u16::<NativeEndian>(0)?; // charactaristics (TODO: fix for 32 bit)
+    buffer.write_u16::<NativeEndian>(0)?; // SizeOfOptionalHeader: always 0 for objects
+    buffer.write_u16::<NativeEndian>(arch.file_characteristics())?; // Characteristics
 
     // first section header
     buffer.write_all(b".idata$2")?;
     buffer.write_u32::<NativeEndian>(0)?; // VirtualSize: always 0 for libs
     buffer.write_u32::<NativeEndian>(0)?; // always 0 for libs
-    buffer.write_u32::<NativeEndian>(0x14)?; // section size
+    buffer.write_u32::<NativeEndian>(IMPORT_DESCRIPTOR_LEN as u32)?; // section size
     buffer.write_u32::<NativeEndian>((COFF_HEADER_LEN + 
         N_SECTIONS as usize * COFF_SECTION_HEADER_LEN) as u32)?; // start of section
     buffer.write_u32::<NativeEndian>((COFF_HEADER_LEN + 
-            N_SECTIONS as usize * COFF_SECTION_HEADER_LEN + 0x14) as u32)?; // start of relocs
+            N_SECTIONS as usize * COFF_SECTION_HEADER_LEN + IMPORT_DESCRIPTOR_LEN) as u32)?; // start of relocs
     buffer.write_u32::<NativeEndian>(0)?; // always 0 
     buffer.write_u16::<NativeEndian>(N_RECLOCATIONS)?;
     buffer.write_u16::<NativeEndian>(0)?;
-    buffer.write_u32::<NativeEndian>(0xC0300040)?; // TODO: label bitflags
+    buffer.write_u32::<NativeEndian>(idata_characteristics(IMAGE_SCN_ALIGN_4BYTES, kernel_mode))?;
 
     // second section header
     buffer.write_all(b".idata$6")?;
@@ -314,51 +1527,41 @@ fn build_import_descriptor(archive_name: &str) -> io::Result<(String, Vec<u8>)>
     buffer.write_u32::<NativeEndian>(0)?; // always 0 
     buffer.write_u16::<NativeEndian>(0)?;
     buffer.write_u16::<NativeEndian>(0)?;
-    buffer.write_u32::<NativeEndian>(0xC0200040)?; // TODO: label bitflags
+    buffer.write_u32::<NativeEndian>(idata_characteristics(IMAGE_SCN_ALIGN_2BYTES, kernel_mode))?;
 
     // .idata$2
-    buffer.write_all(&[0; 0x14])?;
+    buffer.write_all(&[0; IMPORT_DESCRIPTOR_LEN])?;
 
     //relocs
     //name rva
     buffer.write_u32::<NativeEndian>(0x0C)?;
     buffer.write_u32::<NativeEndian>(2)?;
-    buffer.write_u16::<NativeEndian>(0x03)?; // IMAGE_REL_AMD64_ADDR32NB 
+    buffer.write_u16::<NativeEndian>(arch.rva_relocation())?;
     //import lookup table rva
     buffer.write_u32::<NativeEndian>(0x00)?;
     buffer.write_u32::<NativeEndian>(3)?;
-    buffer.write_u16::<NativeEndian>(0x03)?; // IMAGE_REL_AMD64_ADDR32NB 
+    buffer.write_u16::<NativeEndian>(arch.rva_relocation())?;
     //import addr table rva
     buffer.write_u32::<NativeEndian>(0x10)?;
     buffer.write_u32::<NativeEndian>(4)?;
-    buffer.write_u16::<NativeEndian>(0x03)?; // IMAGE_REL_AMD64_ADDR32NB 
+    buffer.write_u16::<NativeEndian>(arch.rva_relocation())?;
 
     // .idata$6
     buffer.write_all(archive_name.as_bytes())?;
     buffer.write_u8(b'\0')?;
 
-    let mut string_start = 4;
     let mut string_table = vec![];
 
     // symbol table
-    write_symbol(&mut buffer, SymbolName::Offset(string_start),1, IMAGE_SYM_CLASS_EXTERNAL)?;
-    write_symbol(&mut buffer, SymbolName::Name(".idata$2"), 1, IMAGE_SYM_CLASS_SECTION)?;
-    write_symbol(&mut buffer, SymbolName::Name(".idata$6"),2, IMAGE_SYM_CLASS_STATIC)?;
-    write_symbol(&mut buffer, SymbolName::Name(".idata$4"),0, IMAGE_SYM_CLASS_SECTION)?;
-    write_symbol(&mut buffer, SymbolName::Name(".idata$5"),0, IMAGE_SYM_CLASS_SECTION)?;
-    string_start += import_desc_name.len();
-    string_table.write_all(import_desc_name.as_bytes())?;
-    string_table.write_u8(b'\0')?;
-    write_symbol(&mut buffer, SymbolName::Offset(string_start),0, IMAGE_SYM_CLASS_EXTERNAL)?;
-    string_start += null_import_data.len();
-    string_table.write_all( null_import_data.as_bytes())?;
-    string_table.write_u8(b'\0')?;
-    write_symbol(&mut buffer, SymbolName::Offset(string_start),0, IMAGE_SYM_CLASS_EXTERNAL)?;
-    string_table.write_all( null_thunk_data.as_bytes())?;
-    string_table.write_u8(b'\0')?;
-
-    buffer.write_u32::<NativeEndian>(string_table.len() as u32)?;
-    buffer.write_all(&string_table)?;
+    write_symbol(&mut buffer, symbol_name(&import_desc_name, &mut string_table), 1, IMAGE_SYM_CLASS_EXTERNAL)?;
+    write_symbol(&mut buffer, symbol_name(".idata$2", &mut string_table), 1, IMAGE_SYM_CLASS_SECTION)?;
+    write_symbol(&mut buffer, symbol_name(".idata$6", &mut string_table), 2, IMAGE_SYM_CLASS_STATIC)?;
+    write_symbol(&mut buffer, symbol_name(".idata$4", &mut string_table), 0, IMAGE_SYM_CLASS_SECTION)?;
+    write_symbol(&mut buffer, symbol_name(".idata$5", &mut string_table), 0, IMAGE_SYM_CLASS_SECTION)?;
+    write_symbol(&mut buffer, symbol_name(&null_import_data, &mut string_table), 0, IMAGE_SYM_CLASS_EXTERNAL)?;
+    write_symbol(&mut buffer, symbol_name(&null_thunk_data, &mut string_table), 0, IMAGE_SYM_CLASS_EXTERNAL)?;
+
+    write_string_table(&mut buffer, &string_table)?;
     if buffer.len() % 2 != 0 {
         buffer.write_u8(b'\0')?;
     }
@@ -366,7 +1569,7 @@ fn build_import_descriptor(archive_name: &str) -> io::Result<(String, Vec<u8>)>
     Ok((import_desc_name, buffer))
 }
 
-fn build_null_import_descriptor() -> io::Result<(String, Vec<u8>)> {
+fn build_null_import_descriptor(arch: TargetArch, kernel_mode: bool, timestamp: u32) -> io::Result<(String, Vec<u8>)> {
     let null_import_data = "__NULL_IMPORT_DESCRIPTOR".to_owned();
 
     // import descriptor
@@ -374,45 +1577,41 @@ fn build_null_import_descriptor() -> io::Result<(String, Vec<u8>)> {
     const N_SYMBOLS: u32 = 1;
 
     let mut buffer = vec![];
-    buffer.write_u16::<NativeEndian>(arch())?;
+    buffer.write_u16::<NativeEndian>(arch.machine())?;
     buffer.write_u16::<NativeEndian>(N_SECTIONS)?;
-    buffer.write_u32::<NativeEndian>(0)?; // TIMESTAMP
+    buffer.write_u32::<NativeEndian>(timestamp)?; // TIMESTAMP
 
     let data_len = COFF_HEADER_LEN + N_SECTIONS as usize * COFF_SECTION_HEADER_LEN +
         // .idata$3
-        20;
+        IMPORT_DESCRIPTOR_LEN;
     
     buffer.write_u32::<NativeEndian>(data_len as u32)?; // aka: symbol table start
     buffer.write_u32::<NativeEndian>(N_SYMBOLS)?;
-    buffer.write_u16::<NativeEndian>(0)?; // always 0 for libs
-    buffer.write_u16::<NativeEndian>(0)?; // charactaristics (TODO: fix for 32 bit)
+    buffer.write_u16::<NativeEndian>(0)?; // SizeOfOptionalHeader: always 0 for objects
+    buffer.write_u16::<NativeEndian>(arch.file_characteristics())?; // Characteristics
 
     // first section header
     buffer.write_all(b".idata$3")?;
     buffer.write_u32::<NativeEndian>(0)?; // VirtualSize: always 0 for libs
     buffer.write_u32::<NativeEndian>(0)?; // always 0 for libs
-    buffer.write_u32::<NativeEndian>(0x14)?; // section size
+    buffer.write_u32::<NativeEndian>(IMPORT_DESCRIPTOR_LEN as u32)?; // section size
     buffer.write_u32::<NativeEndian>((COFF_HEADER_LEN + 
         N_SECTIONS as usize * COFF_SECTION_HEADER_LEN) as u32)?; // start of section
     buffer.write_u32::<NativeEndian>(0)?; // start of relocs
     buffer.write_u32::<NativeEndian>(0)?; // always 0 
     buffer.write_u16::<NativeEndian>(0)?;
     buffer.write_u16::<NativeEndian>(0)?;
-    buffer.write_u32::<NativeEndian>(0xC0300040)?; // TODO: label bitflags
+    buffer.write_u32::<NativeEndian>(idata_characteristics(IMAGE_SCN_ALIGN_4BYTES, kernel_mode))?;
 
     // .idata$3
-    buffer.write_all(&[0; 0x14])?;
+    buffer.write_all(&[0; IMPORT_DESCRIPTOR_LEN])?;
 
-    let string_start = 4;
     let mut string_table = vec![];
 
     // symbol table
-    write_symbol(&mut buffer, SymbolName::Offset(string_start),1, IMAGE_SYM_CLASS_EXTERNAL)?;
-    string_table.write_all( null_import_data.as_bytes())?;
-    string_table.write_u8(b'\0')?;
+    write_symbol(&mut buffer, symbol_name(&null_import_data, &mut string_table), 1, IMAGE_SYM_CLASS_EXTERNAL)?;
 
-    buffer.write_u32::<NativeEndian>(string_table.len() as u32)?;
-    buffer.write_all(&string_table)?;
+    write_string_table(&mut buffer, &string_table)?;
     if buffer.len() % 2 != 0 {
         buffer.write_u8(b'\0')?;
     }
@@ -420,26 +1619,21 @@ fn build_null_import_descriptor() -> io::Result<(String, Vec<u8>)> {
     Ok((null_import_data, buffer))
 }
 
-fn build_null_thunk_data(archive_name: &str) -> io::Result<(String, Vec<u8>)> {
-    let name = if archive_name.ends_with(".dll") {
-        &archive_name[.. archive_name.len() - 4]
-    } else {
-        &archive_name
-    };
+fn build_null_thunk_data(archive_name: &str, arch: TargetArch, kernel_mode: bool, timestamp: u32) -> io::Result<(String, Vec<u8>)> {
+    let name = dll_stem(archive_name);
 
     let null_thunk_data = format!("\u{7F}{}_NULL_THUNK_DATA", name);
 
-    //todo: 32bit
-    let va_size = 8;
+    let va_size = arch.pointer_size();
 
     // import descriptor
     const N_SECTIONS: u16 = 2;
     const N_SYMBOLS: u32 = 1;
 
     let mut buffer = vec![];
-    buffer.write_u16::<NativeEndian>(arch())?;
+    buffer.write_u16::<NativeEndian>(arch.machine())?;
     buffer.write_u16::<NativeEndian>(N_SECTIONS)?;
-    buffer.write_u32::<NativeEndian>(0)?; // TIMESTAMP
+    buffer.write_u32::<NativeEndian>(timestamp)?; // TIMESTAMP
 
     let data_len = COFF_HEADER_LEN + N_SECTIONS as usize * COFF_SECTION_HEADER_LEN +
         // .idata$5
@@ -449,8 +1643,8 @@ fn build_null_thunk_data(archive_name: &str) -> io::Result<(String, Vec<u8>)> {
     
     buffer.write_u32::<NativeEndian>(data_len as u32)?; // aka: symbol table start
     buffer.write_u32::<NativeEndian>(N_SYMBOLS)?;
-    buffer.write_u16::<NativeEndian>(0)?; // always 0 for libs
-    buffer.write_u16::<NativeEndian>(0)?; // charactaristics (TODO: fix for 32 bit)
+    buffer.write_u16::<NativeEndian>(0)?; // SizeOfOptionalHeader: always 0 for objects
+    buffer.write_u16::<NativeEndian>(arch.file_characteristics())?; // Characteristics
 
     // first section header
     buffer.write_all(b".idata$5")?;
@@ -463,7 +1657,7 @@ fn build_null_thunk_data(archive_name: &str) -> io::Result<(String, Vec<u8>)> {
     buffer.write_u32::<NativeEndian>(0)?; // always 0 
     buffer.write_u16::<NativeEndian>(0)?;
     buffer.write_u16::<NativeEndian>(0)?;
-    buffer.write_u32::<NativeEndian>(0xC0400040)?; // TODO: label bitflags
+    buffer.write_u32::<NativeEndian>(idata_characteristics(IMAGE_SCN_ALIGN_8BYTES, kernel_mode))?;
 
     // second section header
     buffer.write_all(b".idata$4")?;
@@ -476,24 +1670,20 @@ fn build_null_thunk_data(archive_name: &str) -> io::Result<(String, Vec<u8>)> {
     buffer.write_u32::<NativeEndian>(0)?; // always 0 
     buffer.write_u16::<NativeEndian>(0)?;
     buffer.write_u16::<NativeEndian>(0)?;
-    buffer.write_u32::<NativeEndian>(0xC0400040)?; // TODO: label bitflags
+    buffer.write_u32::<NativeEndian>(idata_characteristics(IMAGE_SCN_ALIGN_8BYTES, kernel_mode))?;
 
     // .idata$5, ILT
-    buffer.write_u64::<NativeEndian>(0)?;
+    buffer.write_all(&vec![0; va_size])?;
 
     // .idata$4, IAT
-    buffer.write_u64::<NativeEndian>(0)?;
+    buffer.write_all(&vec![0; va_size])?;
 
     //symbols
-    let string_start = 4;
     let mut string_table = vec![];
 
-    write_symbol(&mut buffer, SymbolName::Offset(string_start),1, IMAGE_SYM_CLASS_EXTERNAL)?;
-    string_table.write_all( null_thunk_data.as_bytes())?;
-    string_table.write_u8(b'\0')?;
+    write_symbol(&mut buffer, symbol_name(&null_thunk_data, &mut string_table), 1, IMAGE_SYM_CLASS_EXTERNAL)?;
 
-    buffer.write_u32::<NativeEndian>(string_table.len() as u32)?;
-    buffer.write_all(&string_table)?;
+    write_string_table(&mut buffer, &string_table)?;
     if buffer.len() % 2 != 0 {
         buffer.write_u8(b'\0')?;
     }
@@ -502,15 +1692,115 @@ fn build_null_thunk_data(archive_name: &str) -> io::Result<(String, Vec<u8>)> {
 }
 
 
-enum SymbolName<'a> {
+fn build_comment_object(text: &str, arch: TargetArch, timestamp: u32) -> io::Result<Vec<u8>> {
+    const N_SECTIONS: u16 = 1;
+    const N_SYMBOLS: u32 = 0;
+
+    let mut buffer = vec![];
+    buffer.write_u16::<NativeEndian>(arch.machine())?;
+    buffer.write_u16::<NativeEndian>(N_SECTIONS)?;
+    buffer.write_u32::<NativeEndian>(timestamp)?; // TIMESTAMP
+
+    let data_len = COFF_HEADER_LEN + N_SECTIONS as usize * COFF_SECTION_HEADER_LEN +
+        // .comment
+        text.len();
+
+    buffer.write_u32::<NativeEndian>(data_len as u32)?; // aka: symbol table start
+    buffer.write_u32::<NativeEndian>(N_SYMBOLS)?;
+    buffer.write_u16::<NativeEndian>(0)?; // SizeOfOptionalHeader: always 0 for objects
+    buffer.write_u16::<NativeEndian>(arch.file_characteristics())?; // Characteristics
+
+    buffer.write_all(b".comment")?;
+    buffer.write_u32::<NativeEndian>(0)?; // VirtualSize: always 0 for libs
+    buffer.write_u32::<NativeEndian>(0)?; // always 0 for libs
+    buffer.write_u32::<NativeEndian>(text.len() as u32)?; // section size
+    buffer.write_u32::<NativeEndian>((COFF_HEADER_LEN +
+        N_SECTIONS as usize * COFF_SECTION_HEADER_LEN) as u32)?; // start of section
+    buffer.write_u32::<NativeEndian>(0)?; // start of relocs
+    buffer.write_u32::<NativeEndian>(0)?; // always 0
+    buffer.write_u16::<NativeEndian>(0)?;
+    buffer.write_u16::<NativeEndian>(0)?;
+    buffer.write_u32::<NativeEndian>(IMAGE_SCN_LNK_INFO | IMAGE_SCN_LNK_REMOVE | IMAGE_SCN_ALIGN_1BYTES)?;
+
+    // .comment
+    buffer.write_all(text.as_bytes())?;
+
+    write_string_table(&mut buffer, &[])?;
+
+    Ok(buffer)
+}
+
+// an object with no sections: `target` as an undefined external, and `alias` as a weak
+// external defaulting to it
+fn build_weak_external_object(alias: &str, target: &str, search: WeakSearch, arch: TargetArch, timestamp: u32) -> io::Result<Vec<u8>> {
+    const N_SECTIONS: u16 = 0;
+    const N_SYMBOLS: u32 = 3;
+
+    let mut buffer = vec![];
+    buffer.write_u16::<NativeEndian>(arch.machine())?;
+    buffer.write_u16::<NativeEndian>(N_SECTIONS)?;
+    buffer.write_u32::<NativeEndian>(timestamp)?; // TIMESTAMP
+    buffer.write_u32::<NativeEndian>(COFF_HEADER_LEN as u32)?; // aka: symbol table start
+    buffer.write_u32::<NativeEndian>(N_SYMBOLS)?;
+    buffer.write_u16::<NativeEndian>(0)?; // SizeOfOptionalHeader: always 0 for objects
+    buffer.write_u16::<NativeEndian>(arch.file_characteristics())?; // Characteristics
+
+    let mut string_table = vec![];
+    write_symbol(&mut buffer, symbol_name(target, &mut string_table), 0, IMAGE_SYM_CLASS_EXTERNAL)?;
+    write_symbol_with_aux(&mut buffer, symbol_name(alias, &mut string_table), 0, IMAGE_SYM_CLASS_WEAK_EXTERNAL, 1)?;
+    write_weak_external_aux(&mut buffer, 0, search)?;
+
+    write_string_table(&mut buffer, &string_table)?;
+
+    Ok(buffer)
+}
+
+pub enum SymbolName<'a> {
     Name(&'a str),
     Offset(usize),
 }
 
-fn write_symbol<W: Write>(buf: &mut W, name: SymbolName, section: u16, sym_ty: u8) -> io::Result<()> {
+// names of up to 8 bytes are stored inline, longer ones go in the string table
+fn symbol_name<'a>(name: &'a str, string_table: &mut Vec<u8>) -> SymbolName<'a> {
+    if name.len() <= 8 {
+        SymbolName::Name(name)
+    } else {
+        // offsets count the 4 byte size field that starts the table
+        let offset = 4 + string_table.len();
+        string_table.extend_from_slice(name.as_bytes());
+        string_table.push(b'\0');
+        SymbolName::Offset(offset)
+    }
+}
+
+fn write_string_table<W: Write>(buf: &mut W, string_table: &[u8]) -> io::Result<()> {
+    // the size includes the size field itself, so an empty table is just `4`
+    buf.write_u32::<NativeEndian>(4 + string_table.len() as u32)?;
+    buf.write_all(string_table)
+}
+
+/// Writes an 18 byte COFF symbol record with the given `IMAGE_SYM_CLASS_*` storage class.
+///
+/// `SymbolName::Name` is stored inline and must be at most 8 bytes; longer names go in the
+/// string table and are passed as `SymbolName::Offset`.
+pub fn write_symbol<W: Write>(buf: &mut W, name: SymbolName, section: u16, sym_ty: u8) -> io::Result<()> {
+    write_symbol_with_aux(buf, name, section, sym_ty, 0)
+}
+
+/// Like [`write_symbol`], but sets NumberOfAuxSymbols to `aux`, the number of auxiliary
+/// records the caller writes right after this one.
+pub fn write_symbol_with_aux<W: Write>(buf: &mut W, name: SymbolName, section: u16, sym_ty: u8, aux: u8) -> io::Result<()> {
     match name {
         SymbolName::Name(name) => {
-            buf.write_all(&name.as_bytes()[0..8])?;
+            if name.len() > 8 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("symbol name `{}` is longer than 8 bytes", name),
+                ));
+            }
+            let mut short_name = [0; 8];
+            short_name[..name.len()].copy_from_slice(name.as_bytes());
+            buf.write_all(&short_name)?;
         },
         SymbolName::Offset(o) => {
             buf.write_u32::<NativeEndian>(0x00)?;
@@ -522,7 +1812,1296 @@ fn write_symbol<W: Write>(buf: &mut W, name: SymbolName, section: u16, sym_ty: u
     buf.write_u16::<NativeEndian>(section)?;
     buf.write_u16::<NativeEndian>(0x00)?;
     buf.write_u8(sym_ty)?;
-    buf.write_u8(0x00)?;
+    buf.write_u8(aux)?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Writes the auxiliary record following a weak external symbol: the symbol table index of
+/// the default symbol, and the search type in the Characteristics field. The symbol itself
+/// must be written with [`write_symbol_with_aux`] and an `aux` of 1.
+pub fn write_weak_external_aux<W: Write>(buf: &mut W, tag_index: u32, search: WeakSearch) -> io::Result<()> {
+    buf.write_u32::<NativeEndian>(tag_index)?;
+    buf.write_u32::<NativeEndian>(search.characteristics())?;
+    buf.write_all(&[0; 10])
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::ByteOrder;
+
+    #[test]
+    fn renamed_imports_define_the_rust_name() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_names_with(&["add"], |n| format!("rt_{}", n))
+            .import_name("sub", "sub");
+        let archive = builder.coff_archive().unwrap();
+        let members = &archive.sections[archive.sections.len() - 2..];
+
+        // the linker defines `__imp_rt_add` and `rt_add` from the name string
+        let (symbol, import, dll) = parse::parse_short_import(&members[0]).unwrap();
+        assert_eq!((symbol.as_str(), import, dll.as_str()), ("rt_add", Import::Name("add".to_owned()), "mydll.dll"));
+
+        let raw = parse::raw_short_import(&members[0]).unwrap();
+        assert_eq!((raw.import_type >> 2) & 0x7, ImportNameType::ExportAs.bits());
+        assert_eq!(NativeEndian::read_u32(&members[0][12..16]) as usize, "rt_add\0mydll.dll\0add\0".len());
+        assert_eq!(&members[0][SHORT_IMPORT_HEADER_LEN..], b"rt_add\0mydll.dll\0add\0");
+
+        // an unrenamed import stays a plain IMPORT_OBJECT_NAME
+        let raw = parse::raw_short_import(&members[1]).unwrap();
+        assert_eq!((raw.name, raw.export_name), ("sub", None));
+        assert_eq!((raw.import_type >> 2) & 0x7, ImportNameType::Name.bits());
+    }
+
+    #[test]
+    fn import_names_with_transform() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .import_names_with(&["add", "sub"], |n| format!("rt_{}", n));
+
+        assert_eq!(builder.imports, vec![
+            ImportEntry::new("rt_add", Import::Name("add".to_owned())),
+            ImportEntry::new("rt_sub", Import::Name("sub".to_owned())),
+        ]);
+    }
+
+    #[test]
+    fn dll_extension_added_when_missing() {
+        let mut archive = CoffArchiveBuilder::new("kernel32", TargetArch::X86_64);
+        archive.add_import_descriptors(true).unwrap();
+        archive.add_short_import(&ImportEntry::new("add", Import::Name("add".to_owned()))).unwrap();
+
+        assert!(archive.symbols.contains_key("__IMPORT_DESCRIPTOR_kernel32"));
+        assert!(archive.sections[3].ends_with(b"add\0kernel32.dll\0"));
+
+        let archive = CoffArchiveBuilder::new("mydll.a.dll", TargetArch::X86_64);
+        assert_eq!(archive.archive_name, "mydll.a.dll");
+    }
+
+    #[test]
+    fn short_symbol_names_are_inlined() {
+        let mut string_table = vec![];
+
+        let mut symbol = vec![];
+        write_symbol(&mut symbol, symbol_name("add", &mut string_table), 1, IMAGE_SYM_CLASS_EXTERNAL).unwrap();
+        assert_eq!(&symbol[0..8], b"add\0\0\0\0\0");
+        assert!(string_table.is_empty());
+
+        let mut symbol = vec![];
+        write_symbol(&mut symbol, symbol_name("__NULL_IMPORT_DESCRIPTOR", &mut string_table), 1, IMAGE_SYM_CLASS_EXTERNAL).unwrap();
+        assert_eq!(&symbol[0..8], &[0, 0, 0, 0, 4, 0, 0, 0]);
+        assert_eq!(string_table, b"__NULL_IMPORT_DESCRIPTOR\0");
+
+        let mut table = vec![];
+        write_string_table(&mut table, &[]).unwrap();
+        assert_eq!(table, [4, 0, 0, 0]);
+    }
+
+    #[test]
+    fn null_import_descriptor_emitted_once() {
+        let members = |emit| {
+            let mut archive = CoffArchiveBuilder::new("mydll.dll", TargetArch::X86_64);
+            archive.add_import_descriptors(emit).unwrap();
+            archive.add_short_import(&ImportEntry::new("add", Import::Name("add".to_owned()))).unwrap();
+            archive.sections.iter()
+                .filter(|data| data.windows(24).any(|w| w == b"__NULL_IMPORT_DESCRIPTOR"))
+                .count()
+        };
+
+        // the import descriptor references the symbol, the null descriptor defines it
+        assert_eq!(members(true), 2);
+        assert_eq!(members(false), 1);
+
+        let mut archive = CoffArchiveBuilder::new("mydll.dll", TargetArch::X86_64);
+        archive.add_import_descriptors(false).unwrap();
+        assert!(!archive.symbols.contains_key("__NULL_IMPORT_DESCRIPTOR"));
+        assert!(archive.symbols.contains_key("__IMPORT_DESCRIPTOR_mydll"));
+    }
+
+    #[test]
+    fn fluent_import_kind() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .import_name("x", "x").as_data()
+            .import_name("y", "y")
+            .import_name("z", "z").as_const();
+
+        let kinds: Vec<_> = builder.imports.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [ImportKind::Data, ImportKind::Code, ImportKind::Const]);
+
+        let mut archive = CoffArchiveBuilder::new("mydll.dll", TargetArch::X86_64);
+        archive.add_short_import(&builder.imports[0]).unwrap();
+
+        let import_type = NativeEndian::read_u16(&archive.sections[0][18..20]);
+        assert_eq!(import_type & 0x3, 0x1);
+        assert!(archive.symbols.contains_key("__imp_x"));
+        assert!(!archive.symbols.contains_key("x"));
+    }
+
+    #[test]
+    fn members_aligned() {
+        let build = |align| ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .member_alignment(align)
+            .import_ordinal("mult", 3)
+            .import_name("add", "add")
+            .import_name("sub", "sub")
+            .build();
+
+        let lib = build(16);
+        let member_count = NativeEndian::read_u32(&lib[8 + 60 + first_linker_len(&lib) + 60..]) as usize;
+        assert_eq!(member_count, 6);
+
+        let offsets_start = 8 + 60 + first_linker_len(&lib) + 60 + 4;
+        for i in 0..member_count {
+            let offset = NativeEndian::read_u32(&lib[offsets_start + 4 * i..]) as usize;
+            assert_eq!(offset % 16, 0);
+            assert_eq!(&lib[offset + 58..offset + 60], b"`\n");
+        }
+        assert_eq!(parse::Members::new(&lib).unwrap().count(), 8);
+
+        // default alignment is unchanged by the knob
+        assert_eq!(build(2), ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_ordinal("mult", 3)
+            .import_name("add", "add")
+            .import_name("sub", "sub")
+            .build());
+    }
+
+    fn first_linker_len(lib: &[u8]) -> usize {
+        let size: usize = std::str::from_utf8(&lib[8 + 48..8 + 58]).unwrap().trim_end().parse().unwrap();
+        size + size % 2
+    }
+
+    #[test]
+    fn kernel_mode_section_characteristics() {
+        let section_flags = |kernel_mode| {
+            let mut archive = CoffArchiveBuilder::new("ntoskrnl.exe", TargetArch::X86_64);
+            archive.kernel_mode = kernel_mode;
+            archive.add_import_descriptors(true).unwrap();
+
+            // characteristics is the last field of the first section header
+            let start = COFF_HEADER_LEN + COFF_SECTION_HEADER_LEN - 4;
+            archive.sections.iter()
+                .map(|data| NativeEndian::read_u32(&data[start..start + 4]))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(section_flags(false), [0xC030_0040, 0xC030_0040, 0xC040_0040]);
+        assert_eq!(section_flags(true), [0xC830_0040, 0xC830_0040, 0xC840_0040]);
+    }
+
+    #[test]
+    fn total_symbol_count_matches_build() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_ordinal("mult", 3)
+            .import_name("add", "add")
+            .import_name("value", "value").as_data();
+
+        assert_eq!(builder.total_symbol_count(), 3 + 2 + 2 + 1);
+
+        let (first, _) = parse_linker_members(&builder.clone().build()).unwrap();
+        assert_eq!(first.symbols.len(), builder.total_symbol_count());
+
+        let builder = builder.null_import_descriptor(false);
+        let (first, _) = parse_linker_members(&builder.clone().build()).unwrap();
+        assert_eq!(first.symbols.len(), builder.total_symbol_count());
+
+        // a repeated rust name can't be counted like the lib, so it doesn't build
+        let builder = builder.import_name("add", "add2");
+        assert_eq!(builder.clone().try_build().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(builder.write_to_seek(&mut io::Cursor::new(vec![])).is_err());
+    }
+
+    #[test]
+    fn symbol_storage_class() {
+        let mut symbol = vec![];
+        write_symbol(&mut symbol, SymbolName::Name("label"), 1, IMAGE_SYM_CLASS_LABEL).unwrap();
+        assert_eq!(symbol.len(), 18);
+        assert_eq!(symbol[16], 6);
+
+        // a weak external announces the aux record that follows it
+        let mut weak = vec![];
+        write_symbol_with_aux(&mut weak, SymbolName::Name("alias"), 0, IMAGE_SYM_CLASS_WEAK_EXTERNAL, 1).unwrap();
+        write_weak_external_aux(&mut weak, 0, WeakSearch::Alias).unwrap();
+        assert_eq!(weak.len(), 2 * 18);
+        assert_eq!(weak[17], 1);
+
+        // names that don't fit inline are rejected rather than truncated
+        let err = write_symbol(&mut vec![], SymbolName::Name("long_label"), 1, IMAGE_SYM_CLASS_LABEL).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // the descriptor uses the classes lib.exe emits
+        let (_, data) = build_import_descriptor("mydll.dll", TargetArch::X86_64, false, 0).unwrap();
+        let symbol_table = NativeEndian::read_u32(&data[8..12]) as usize;
+        let classes: Vec<_> = (0..7).map(|i| data[symbol_table + 18 * i + 16]).collect();
+        assert_eq!(classes, [2, 104, 3, 104, 104, 2, 2]);
+    }
+
+    #[test]
+    fn manual_descriptors_skip_generated_members() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .manual_descriptors(true)
+            .import_name("add", "add");
+
+        assert_eq!(builder.exported_symbols(), ["__imp_add", "add"]);
+        assert_eq!(builder.total_symbol_count(), 2);
+
+        let (_, second) = parse_linker_members(&builder.clone().build()).unwrap();
+        assert_eq!(second.offsets.len(), 1);
+
+        let (name, descriptor) = build_import_descriptor("mydll.dll", TargetArch::X86_64, false, 0).unwrap();
+        let builder = builder.add_object(descriptor, &[&name]);
+        assert_eq!(builder.exported_symbols(), ["__IMPORT_DESCRIPTOR_mydll", "__imp_add", "add"]);
+        assert_eq!(builder.total_symbol_count(), 3);
+
+        let (_, second) = parse_linker_members(&builder.build()).unwrap();
+        assert_eq!(second.offsets.len(), 2);
+    }
+
+    #[test]
+    fn short_import_layout_matches_reference() {
+        // members from `llvm-dlltool -m i386:x86-64` for `add` and `mult @3 NONAME`
+        let add: &[u8] = b"\x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\x0e\x00\x00\x00\x00\x00\x04\x00add\0mydll.dll\0";
+        let mult: &[u8] = b"\x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\x0f\x00\x00\x00\x03\x00\x00\x00mult\0mydll.dll\0";
+
+        let mut archive = CoffArchiveBuilder::new("mydll.dll", TargetArch::X86_64);
+        archive.add_short_import(&ImportEntry::new("add", Import::Name("add".to_owned()))).unwrap();
+        archive.add_short_import(&ImportEntry::new("mult", Import::Ordinal(3))).unwrap();
+        assert_eq!(archive.sections[0], add);
+        assert_eq!(archive.sections[1], mult);
+
+        for member in &archive.sections {
+            // import name, then dll name, each NUL terminated, and nothing else
+            let strings = &member[SHORT_IMPORT_HEADER_LEN..];
+            assert_eq!(NativeEndian::read_u32(&member[12..16]) as usize, strings.len());
+
+            let parts: Vec<_> = strings.split(|&b| b == 0).collect();
+            assert_eq!(parts.len(), 3);
+            assert_eq!(parts[1], b"mydll.dll");
+            assert!(parts[2].is_empty());
+        }
+    }
+
+    #[test]
+    fn size_of_data_is_string_length() {
+        let entry = ImportEntry {
+            kind: ImportKind::Data,
+            ..ImportEntry::new("large_table", Import::Name("large_table".to_owned()))
+        };
+
+        let mut archive = CoffArchiveBuilder::new("mydll.dll", TargetArch::X86_64);
+        archive.add_short_import(&entry).unwrap();
+
+        let size_of_data = NativeEndian::read_u32(&archive.sections[0][12..16]);
+        assert_eq!(size_of_data as usize, "large_table\0".len() + "mydll.dll\0".len());
+    }
+
+    #[test]
+    fn with_dll_name_keeps_imports() {
+        let foo1 = ImportLibBuilder::new("foo-1.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_name("add", "add")
+            .import_ordinal("mult", 3);
+        let foo2 = foo1.with_dll_name("foo-2.dll");
+
+        assert_eq!(foo2.imports, foo1.imports);
+        assert_eq!(foo2.exported_symbols()[0], "__IMPORT_DESCRIPTOR_foo-2");
+        assert_eq!(foo1.exported_symbols()[0], "__IMPORT_DESCRIPTOR_foo-1");
+
+        let lib = foo2.build();
+        assert!(lib.windows(14).any(|w| w == b"add\0foo-2.dll\0"));
+        assert!(!lib.windows(9).any(|w| w == b"foo-1.dll"));
+    }
+
+    #[test]
+    fn write_to_seek_matches_build() {
+        for &align in &[2, 16] {
+            let builder = ImportLibBuilder::new("mydll.a.dll")
+                .target_arch(TargetArch::X86_64)
+                .member_alignment(align)
+                .import_ordinal("mult", 3)
+                .import_name("add", "add")
+                .import_name("sub", "sub");
+
+            let mut cursor = io::Cursor::new(vec![]);
+            builder.clone().write_to_seek(&mut cursor).unwrap();
+            assert_eq!(cursor.into_inner(), builder.build());
+        }
+    }
+
+    #[test]
+    fn second_linker_member_indices_are_one_based() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_ordinal("mult", 3)
+            .import_name("add", "add")
+            .import_name("sub", "sub");
+        let (first, second) = parse_linker_members(&builder.build()).unwrap();
+
+        let mut sorted = second.symbols.clone();
+        sorted.sort();
+        assert_eq!(second.symbols, sorted);
+
+        for (symbol, &index) in second.symbols.iter().zip(&second.indices) {
+            assert!(index >= 1 && index as usize <= second.offsets.len());
+
+            let i = first.symbols.iter().position(|s| s == symbol).unwrap();
+            assert_eq!(second.offsets[index as usize - 1], first.offsets[i]);
+        }
+
+        let descriptor = second.symbols.iter().position(|s| s == "__IMPORT_DESCRIPTOR_mydll").unwrap();
+        assert_eq!(second.indices[descriptor], 1);
+    }
+
+    #[test]
+    fn uefi_target_triples() {
+        assert_eq!(TargetArch::from_target_triple("x86_64-unknown-uefi"), Some(TargetArch::X86_64));
+        assert_eq!(TargetArch::from_target_triple("i686-unknown-uefi"), Some(TargetArch::X86));
+        assert_eq!(TargetArch::from_target_triple("aarch64-unknown-uefi"), Some(TargetArch::AArch64));
+        assert_eq!(TargetArch::from_target_triple("wasm32-unknown-unknown"), None);
+
+        let lib = ImportLibBuilder::new("runtime.dll")
+            .target_arch(TargetArch::from_target_triple("x86_64-unknown-uefi").unwrap())
+            .import_name("add", "add")
+            .build();
+        assert_eq!(detected_arch(&lib), Some(TargetArch::X86_64));
+    }
+
+    #[test]
+    fn normalize_dedups_and_sorts() {
+        let mut builder = ImportLibBuilder::new("mydll.dll")
+            .import_name("sub", "sub")
+            .import_name("add", "add")
+            .import_ordinal("sub", 7)
+            .import_ordinal("zero", 0)
+            .import_name("add", "add2");
+
+        let report = builder.normalize();
+
+        let names: Vec<_> = builder.imports.iter().map(|e| e.rust_name.as_str()).collect();
+        assert_eq!(names, ["add", "sub", "zero"]);
+        assert_eq!(builder.imports[0].import, Import::Name("add".to_owned()));
+        assert_eq!(builder.imports[1].import, Import::Name("sub".to_owned()));
+
+        assert_eq!(report.removed, [
+            ("sub".to_owned(), Import::Ordinal(7)),
+            ("add".to_owned(), Import::Name("add2".to_owned())),
+        ]);
+        assert_eq!(report.invalid_ordinals, ["zero"]);
+
+        assert_eq!(builder.normalize(), NormalizeReport { removed: vec![], invalid_ordinals: vec!["zero".to_owned()] });
+    }
+
+    #[test]
+    fn from_dll_path_uses_file_name() {
+        let path = Path::new("target").join("debug").join("foo.dll");
+        let builder = ImportLibBuilder::from_dll_path(&path)
+            .target_arch(TargetArch::X86_64)
+            .import_name("add", "add");
+
+        assert_eq!(builder.name, "foo.dll");
+        assert_eq!(builder.exported_symbols()[0], "__IMPORT_DESCRIPTOR_foo");
+
+        let lib = builder.build();
+        assert!(lib.windows(8).any(|w| w == b"foo.dll\0"));
+        assert!(!lib.windows(6).any(|w| w == b"debug\\" || w == b"debug/"));
+    }
+
+    #[test]
+    fn metadata_comment_is_info_only() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .add_metadata_comment("add: mydll.dll!add")
+            .import_name("add", "add");
+
+        let archive = builder.coff_archive().unwrap();
+        let comment = &archive.sections[3];
+
+        assert_eq!(&comment[COFF_HEADER_LEN..COFF_HEADER_LEN + 8], b".comment");
+        let start = COFF_HEADER_LEN + COFF_SECTION_HEADER_LEN - 4;
+        let characteristics = NativeEndian::read_u32(&comment[start..start + 4]);
+        assert_ne!(characteristics & IMAGE_SCN_LNK_INFO, 0);
+        assert_ne!(characteristics & IMAGE_SCN_LNK_REMOVE, 0);
+        assert!(comment.windows(18).any(|w| w == b"add: mydll.dll!add"));
+
+        assert_eq!(builder.total_symbol_count(), builder.exported_symbols().len());
+    }
+
+    #[test]
+    fn import_object_type_encoding() {
+        use ImportKind::*;
+        use ImportNameType::*;
+
+        let expected = [
+            (Code, Ordinal, 0x0), (Code, Name, 0x4), (Code, NameNoPrefix, 0x8), (Code, NameUndecorate, 0xC), (Code, ExportAs, 0x10),
+            (Data, Ordinal, 0x1), (Data, Name, 0x5), (Data, NameNoPrefix, 0x9), (Data, NameUndecorate, 0xD), (Data, ExportAs, 0x11),
+            (Const, Ordinal, 0x2), (Const, Name, 0x6), (Const, NameNoPrefix, 0xA), (Const, NameUndecorate, 0xE), (Const, ExportAs, 0x12),
+        ];
+
+        for &(kind, name_type, bits) in &expected {
+            assert_eq!(import_object_type(kind, name_type), bits, "{:?} {:?}", kind, name_type);
+        }
+    }
+
+    #[test]
+    fn name_and_ordinal_both() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_name_and_ordinal_both("add", "add", 5);
+
+        assert_eq!(builder.imports, vec![
+            ImportEntry::new("add", Import::Name("add".to_owned())),
+            ImportEntry::new("add_by_ordinal", Import::Ordinal(5)),
+        ]);
+
+        let archive = builder.coff_archive().unwrap();
+        assert_eq!(archive.sections.len(), 3 + 2);
+        assert_eq!(archive.symbols["add"], 4);
+        assert_eq!(archive.symbols["__imp_add"], 4);
+        assert_eq!(archive.symbols["add_by_ordinal"], 5);
+        assert_eq!(archive.symbols["__imp_add_by_ordinal"], 5);
+        assert_eq!(NativeEndian::read_u16(&archive.sections[4][16..18]), 5);
+    }
+
+    #[test]
+    fn final_member_padding() {
+        // the "add" import from "my.dll" is an odd 31 bytes
+        let builder = ImportLibBuilder::new("my.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_name("add", "add");
+
+        let padded = builder.clone().build();
+        let unpadded = builder.clone().pad_final_member(false).build();
+
+        assert_eq!(padded.len() % 2, 0);
+        assert_eq!(*padded.last().unwrap(), 0);
+        assert_eq!(unpadded.len() % 2, 1);
+        assert!(unpadded.ends_with(b"add\0my.dll\0"));
+        assert_eq!(padded[..unpadded.len()], unpadded[..]);
+
+        let mut cursor = io::Cursor::new(vec![]);
+        builder.pad_final_member(false).write_to_seek(&mut cursor).unwrap();
+        assert_eq!(cursor.into_inner(), unpadded);
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn imports_from_csv() {
+        let csv = "rust_name,lib_name,ordinal,kind\n\
+                   add,add,,\n\
+                   mult,,3,code\n\
+                   rt_sub,sub,,\n\
+                   table,,,data\n";
+
+        let builder = ImportLibBuilder::from_csv("mydll.dll", csv.as_bytes()).unwrap();
+        assert_eq!(builder.imports, vec![
+            ImportEntry::new("add", Import::Name("add".to_owned())),
+            ImportEntry::new("mult", Import::Ordinal(3)),
+            ImportEntry::new("rt_sub", Import::Name("sub".to_owned())),
+            ImportEntry { kind: ImportKind::Data, ..ImportEntry::new("table", Import::Name("table".to_owned())) },
+        ]);
+
+        // the renamed row defines `rt_sub` and imports `sub`
+        let archive = builder.target_arch(TargetArch::X86_64).coff_archive().unwrap();
+        let member = &archive.sections[archive.sections.len() - 2];
+        let raw = parse::raw_short_import(member).unwrap();
+        assert_eq!((raw.name, raw.export_name), ("rt_sub", Some("sub")));
+        assert_eq!((raw.import_type >> 2) & 0x7, ImportNameType::ExportAs.bits());
+
+        let bad = "rust_name,lib_name,ordinal,kind\nmult,,70000,\n";
+        assert!(ImportLibBuilder::from_csv("mydll.dll", bad.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn archive_header_is_fixed_size() {
+        let mut header = vec![];
+        write_header(&mut header, &header_name("a_very_long_dll_name.dll"), 0).unwrap();
+        assert_eq!(header.len(), ARCHIVE_HEADER_LEN);
+
+        // multibyte names are cut on a char boundary and padded by bytes
+        let name = header_name("bibliothèque_très_longue.dll");
+        assert_eq!(name, "bibliothèque_t/");
+        assert!(header_name("日本語のライブラリ.dll").len() <= 16);
+
+        let mut header = vec![];
+        write_header(&mut header, &name, 0).unwrap();
+        assert_eq!(header.len(), ARCHIVE_HEADER_LEN);
+
+        let mut header = vec![];
+        write_header(&mut header, &header_name("lib_é.dll"), 0).unwrap();
+        assert_eq!(header.len(), ARCHIVE_HEADER_LEN);
+        assert_eq!(&header[..16], "lib_é.dll/     ".as_bytes());
+    }
+
+    // sizes past the 10 digit field don't fit in a 32-bit usize
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn archive_header_size_field_overflow() {
+        let mut header = vec![];
+        write_header(&mut header, "mydll.dll", 9_999_999_999).unwrap();
+        assert_eq!(header.len(), ARCHIVE_HEADER_LEN);
+
+        let mut header = vec![];
+        let err = write_header(&mut header, "mydll.dll", 10_000_000_000).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(header.is_empty());
+    }
+
+    #[test]
+    fn short_import_header_len() {
+        let mut archive = CoffArchiveBuilder::new("mydll.dll", TargetArch::X86_64);
+        archive.add_short_import(&ImportEntry::new("", Import::Name(String::new()))).unwrap();
+
+        assert_eq!(archive.sections[0].len(), SHORT_IMPORT_HEADER_LEN + "mydll.dll".len() + 2);
+    }
+
+    #[test]
+    fn mixed_mode_native_exports() {
+        // not checked against a real C++/CLI dll, only against what llvm-dlltool emits for
+        // the same native exports: `NativeAdd` and `NativeTable DATA`
+        let native_add: &[u8] = b"\x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\x18\x00\x00\x00\x00\x00\x04\x00NativeAdd\0MixedMode.dll\0";
+        let native_table: &[u8] = b"\x00\x00\xff\xff\x00\x00\x64\x86\x00\x00\x00\x00\x1a\x00\x00\x00\x00\x00\x05\x00NativeTable\0MixedMode.dll\0";
+
+        let builder = ImportLibBuilder::new("MixedMode.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_name("NativeAdd", "NativeAdd")
+            .import_name("NativeTable", "NativeTable").as_data()
+            .import_ordinal("NativeSub", 2);
+
+        let archive = builder.coff_archive().unwrap();
+        let members = &archive.sections[3..];
+        assert_eq!(members[0], native_add);
+        assert_eq!(members[1], native_table);
+
+        // Type and SizeOfData: a code and a data name import, each sized by its own strings
+        let fields = |member: &[u8]| (NativeEndian::read_u16(&member[18..20]), NativeEndian::read_u32(&member[12..16]));
+        assert_eq!(fields(&members[0]), (0x4, 24));
+        assert_eq!(fields(&members[1]), (0x5, 26));
+        assert_eq!(fields(&members[2]).0, 0x0);
+    }
+
+    #[test]
+    fn per_member_timestamps() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .timestamp(0x5000_0000)
+            .import_name("add", "add").import_timestamp(0x6000_0001)
+            .import_name("sub", "sub").import_timestamp(0x6000_0002)
+            .import_name("mul", "mul");
+
+        let stamps = |builder: &ImportLibBuilder| -> Vec<u32> {
+            builder.coff_archive().unwrap().sections.iter()
+                .map(|member| match parse::raw_short_import(member) {
+                    Ok(_) => NativeEndian::read_u32(&member[8..12]),
+                    Err(_) => NativeEndian::read_u32(&member[4..8]),
+                })
+                .collect()
+        };
+
+        assert_eq!(stamps(&builder), [0x5000_0000, 0x5000_0000, 0x5000_0000, 0x6000_0001, 0x6000_0002, 0x5000_0000]);
+        assert_eq!(stamps(&builder.timestamp(0)), [0, 0, 0, 0x6000_0001, 0x6000_0002, 0]);
+    }
+
+    #[test]
+    fn gnu_symbol_table() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .symbol_table(ArSymbolTable::Gnu)
+            .import_ordinal("mult", 3)
+            .import_name("add", "add");
+        let lib = builder.clone().build();
+
+        let mut members = parse::Members::new(&lib).unwrap();
+        let (name, index) = members.next().unwrap();
+        assert_eq!(name, "/");
+        assert_ne!(members.next().unwrap().0, "/");
+
+        let index = parse::parse_first_linker_member(index).unwrap();
+        assert_eq!(index.symbols, builder.exported_symbols());
+        assert_eq!(parse::Members::new(&lib).unwrap().count(), 1 + 5);
+
+        for offset in index.offsets {
+            let offset = offset as usize;
+            assert_eq!(&lib[offset..offset + 10], b"mydll.dll/");
+        }
+
+        let mut cursor = io::Cursor::new(vec![]);
+        builder.clone().write_to_seek(&mut cursor).unwrap();
+        assert_eq!(cursor.into_inner(), lib);
+
+        // a single `/` member is enough to read the lib back
+        let parsed = ImportLibBuilder::from_bytes(&lib).unwrap();
+        assert_eq!(parsed.symbol_table, ArSymbolTable::Gnu);
+        assert_eq!(parsed.imports, builder.imports);
+        assert_eq!(parsed.build(), lib);
+    }
+
+    #[test]
+    fn add_import_matches_helpers() {
+        let builder = || ImportLibBuilder::new("mydll.dll").target_arch(TargetArch::X86_64);
+
+        assert_eq!(
+            builder().add_import("x", Import::Ordinal(5)).build(),
+            builder().import_ordinal("x", 5).build()
+        );
+        assert_eq!(
+            builder().add_import("add", Import::Name("add".to_owned())).build(),
+            builder().import_name("add", "add").build()
+        );
+    }
+
+    #[test]
+    fn forwarded_imports() {
+        let builder = ImportLibBuilder::new("a.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_forwarded("func", "func", "b.func")
+            .import_name("other", "other");
+
+        assert_eq!(builder.forward_target("func"), Some("b.func"));
+        assert_eq!(builder.forward_target("other"), None);
+        assert_eq!(builder.with_dll_name("c.dll").forward_target("func"), Some("b.func"));
+
+        let plain = ImportLibBuilder::new("a.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_name("func", "func")
+            .import_name("other", "other");
+        assert_eq!(builder.build(), plain.build());
+    }
+
+    #[test]
+    fn size_report_sums_to_lib_len() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .add_metadata_comment("generated")
+            .import_ordinal("mult", 3)
+            .import_name("add", "add");
+
+        let report = builder.size_report();
+        assert!(report.second_linker_member > 0);
+        assert!(report.null_import_descriptor > 0);
+        assert!(report.objects > 0);
+        assert_eq!(report.total(), builder.clone().build().len());
+
+        let builder = builder.symbol_table(ArSymbolTable::Gnu).null_import_descriptor(false).pad_final_member(false);
+        let report = builder.size_report();
+        assert_eq!(report.second_linker_member, 0);
+        assert_eq!(report.null_import_descriptor, 0);
+        assert_eq!(report.total(), builder.build().len());
+    }
+
+    #[test]
+    fn rust_imports_are_verbatim() {
+        let symbol = "_RNvCs15kBYyAo9fc_7mycrate3add";
+
+        for &arch in &[TargetArch::X86, TargetArch::X86_64] {
+            let builder = ImportLibBuilder::new("mydll.dll")
+                .target_arch(arch)
+                .import_rust("add", symbol);
+            assert!(builder.exported_symbols().ends_with(&["__imp_add".to_owned(), "add".to_owned()]));
+
+            // the member defines the symbols the index lists, and imports the mangled export
+            let archive = builder.coff_archive().unwrap();
+            let member = archive.sections.last().unwrap();
+            let (rust_name, import, _) = parse::parse_short_import(member).unwrap();
+            assert_eq!((rust_name.as_str(), import), ("add", Import::Name(symbol.to_owned())));
+
+            let raw = parse::raw_short_import(member).unwrap();
+            assert_eq!((raw.import_type >> 2) & 0x7, ImportNameType::ExportAs.bits());
+
+            let plain = ImportLibBuilder::new("mydll.dll").target_arch(arch).import_name("add", symbol);
+            assert_eq!(builder.build(), plain.build());
+        }
+    }
+
+    #[test]
+    fn duplicate_ordinals_are_rejected() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_ordinal("mult", 5)
+            .import_name("add", "add")
+            .import_ordinal("div", 5);
+
+        // only building rejects them, inspecting the builder still works
+        assert!(builder.exported_symbols().contains(&"__imp_div".to_owned()));
+        assert!(builder.size_report().total() > 0);
+
+        let err = builder.clone().try_build().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(builder.write_to_seek(&mut io::Cursor::new(vec![])).is_err());
+
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_ordinal("mult", 5)
+            .import_ordinal("div", 6);
+        assert!(builder.try_build().is_ok());
+    }
+
+    // offsets past 4 GiB can't be represented in a 32-bit usize in the first place
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn offsets_past_4gib_are_rejected() {
+        let offsets = member_offsets(ARCHIVE_SIG.len(), vec![0x7FFF_FF00, 0x7FFF_FF00, 0x200], 2);
+        assert!(offsets[2] <= u32::MAX as usize);
+        assert!(offsets[3] > u32::MAX as usize);
+
+        let symbols = |index: usize| vec![("add".to_owned(), index)];
+        assert!(first_linker_member(&symbols(3), &offsets[..3]).is_ok());
+        assert!(second_linker_member(&symbols(3), &offsets[..3]).is_ok());
+
+        let err = first_linker_member(&symbols(4), &offsets[..4]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(second_linker_member(&symbols(1), &offsets[..4]).is_err());
+    }
+
+    #[test]
+    fn member_indices_past_u16_are_rejected() {
+        let offsets: Vec<_> = (0..0x10000).map(|i| ARCHIVE_SIG.len() + 2 * i).collect();
+        let symbols = |index: usize| vec![("add".to_owned(), index)];
+        assert!(second_linker_member(&symbols(0xFFFF), &offsets).is_ok());
+
+        let err = second_linker_member(&symbols(0x10000), &offsets).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn from_map_is_sorted() {
+        let map: HashMap<_, _> = vec![
+            ("sub".to_owned(), Import::Name("sub".to_owned())),
+            ("mult".to_owned(), Import::Ordinal(3)),
+            ("add".to_owned(), Import::Name("add".to_owned())),
+        ].into_iter().collect();
+
+        let builder = ImportLibBuilder::from_map("mydll.dll", map.clone()).target_arch(TargetArch::X86_64);
+        assert_eq!(builder.imports, vec![
+            ImportEntry::new("add", Import::Name("add".to_owned())),
+            ImportEntry::new("mult", Import::Ordinal(3)),
+            ImportEntry::new("sub", Import::Name("sub".to_owned())),
+        ]);
+
+        let expected = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_name("add", "add")
+            .import_ordinal("mult", 3)
+            .import_name("sub", "sub");
+        assert_eq!(builder.build(), expected.build());
+    }
+
+    #[test]
+    fn name_import_with_ordinal_hint() {
+        let mut archive = CoffArchiveBuilder::new("mydll.dll", TargetArch::X86_64);
+        let builder = ImportLibBuilder::new("mydll.dll").import_name_with_hint("add", "add", 7);
+        archive.add_short_import(&builder.imports[0]).unwrap();
+
+        let raw = parse::raw_short_import(&archive.sections[0]).unwrap();
+        assert_eq!(raw.name, "add");
+        assert_eq!(raw.ordinal_or_hint, 7);
+        assert_eq!((raw.import_type >> 2) & 0x7, ImportNameType::Name.bits());
+    }
+
+    #[test]
+    fn lint_flags_decorated_names() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .import_name("add", "add")
+            .import_name("foo", "foo@4")
+            .import_name("bar", "?bar@@YAHH@Z")
+            .import_ordinal("mult", 3);
+
+        assert_eq!(builder.lint(), vec![
+            LintWarning::DecoratedName { rust_name: "foo".to_owned(), name: "foo@4".to_owned() },
+            LintWarning::CppMangledName { rust_name: "bar".to_owned(), name: "?bar@@YAHH@Z".to_owned() },
+        ]);
+
+        // flagged pairs still link: the member defines the rust name and imports the export as is
+        let archive = builder.clone().target_arch(TargetArch::X86).coff_archive().unwrap();
+        let (rust_name, import, _) = parse::parse_short_import(&archive.sections[archive.sections.len() - 3]).unwrap();
+        assert_eq!((rust_name.as_str(), import), ("foo", Import::Name("foo@4".to_owned())));
+        assert!(builder.try_build().is_ok());
+    }
+
+    #[test]
+    fn import_regions() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_name("add", "add").in_region("core")
+            .import_ordinal("mult", 3).in_region("optional")
+            .import_name("sub", "sub").in_region("core")
+            .import_name("div", "div");
+
+        assert_eq!(builder.imports_in_region("core"), vec![
+            ("add", &Import::Name("add".to_owned())),
+            ("sub", &Import::Name("sub".to_owned())),
+        ]);
+        assert_eq!(builder.imports_in_region("optional"), vec![("mult", &Import::Ordinal(3))]);
+        assert!(builder.imports_in_region("missing").is_empty());
+
+        let untagged = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_name("add", "add")
+            .import_ordinal("mult", 3)
+            .import_name("sub", "sub")
+            .import_name("div", "div");
+        assert_eq!(builder.build(), untagged.build());
+    }
+
+    #[test]
+    fn coff_header_field_order() {
+        let arch = TargetArch::X86;
+        let objects = vec![
+            build_import_descriptor("mydll.dll", arch, false, 0).unwrap().1,
+            build_null_import_descriptor(arch, false, 0).unwrap().1,
+            build_null_thunk_data("mydll.dll", arch, false, 0).unwrap().1,
+            build_comment_object("generated", arch, 0).unwrap(),
+        ];
+
+        for object in objects {
+            // Machine, NumberOfSections, TimeDateStamp, PointerToSymbolTable, NumberOfSymbols,
+            // SizeOfOptionalHeader, Characteristics
+            assert_eq!(NativeEndian::read_u16(&object[0..2]), arch.machine());
+            assert_eq!(NativeEndian::read_u16(&object[16..18]), 0);
+            assert_eq!(NativeEndian::read_u16(&object[18..20]), arch.file_characteristics());
+            // the first section header follows immediately, as there's no optional header
+            assert_eq!(object[COFF_HEADER_LEN], b'.');
+        }
+    }
+
+    #[test]
+    fn append_to_built_lib() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86)
+            .null_import_descriptor(false)
+            .import_ordinal("mult", 3)
+            .import_name("add", "add").as_data()
+            .import_name_with_hint("sub", "sub", 4);
+
+        let appended = append_to(&builder.clone().build(), &[
+            ("div".to_owned(), Import::Name("div".to_owned())),
+            ("neg".to_owned(), Import::Ordinal(9)),
+        ]).unwrap();
+
+        let expected = builder.clone()
+            .import_name("div", "div")
+            .import_ordinal("neg", 9);
+        assert_eq!(appended, expected.build());
+
+        let parsed = ImportLibBuilder::from_bytes(&appended).unwrap();
+        assert_eq!(parsed.imports.len(), 5);
+        assert!(!parsed.exported_symbols().contains(&"__NULL_IMPORT_DESCRIPTOR".to_owned()));
+
+        let err = append_to(&appended, &[("add".to_owned(), Import::Ordinal(12))]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(append_to(b"not a lib", &[]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn append_to_matches_fresh_build() {
+        // the original lib and the appended import each end both even and odd before padding
+        for &last in &["f", "fx"] {
+            for &added in &["g", "gx"] {
+                let builder = ImportLibBuilder::new("d.dll")
+                    .target_arch(TargetArch::X86_64)
+                    .import_name(last, last);
+
+                let appended = append_to(&builder.clone().build(), &[(added.to_owned(), Import::Name(added.to_owned()))]).unwrap();
+                assert_eq!(appended, builder.import_name(added, added).build());
+                assert_eq!(appended.len() % 2, 0);
+            }
+        }
+    }
+
+    #[test]
+    fn standalone_short_import_matches_member() {
+        let lib = ImportLibBuilder::new("mydll")
+            .target_arch(TargetArch::X86_64)
+            .import_ordinal("mult", 3)
+            .import_name("add", "add")
+            .build();
+
+        let members: Vec<_> = parse::Members::new(&lib).unwrap().map(|(_, data)| data).collect();
+        let arch = TargetArch::X86_64;
+        assert_eq!(short_import_object("mydll", "mult", &Import::Ordinal(3), arch), members[members.len() - 2]);
+        assert_eq!(short_import_object("mydll", "add", &Import::Name("add".to_owned()), arch), members[members.len() - 1]);
+    }
+
+    #[test]
+    fn weak_alias_search_types() {
+        for &(search, characteristics) in &[(WeakSearch::NoLibrary, 1), (WeakSearch::Library, 2), (WeakSearch::Alias, 3)] {
+            let builder = ImportLibBuilder::new("mydll.dll")
+                .target_arch(TargetArch::X86_64)
+                .import_name("add", "add")
+                .weak_alias("my_add", "add", search);
+            assert!(builder.exported_symbols().contains(&"my_add".to_owned()));
+            assert_eq!(builder.total_symbol_count(), builder.exported_symbols().len());
+
+            let archive = builder.coff_archive().unwrap();
+            let object = &archive.sections[3];
+
+            let symbols = &object[COFF_HEADER_LEN..];
+            assert_eq!(&symbols[0..8], b"add\0\0\0\0\0");
+            assert_eq!(symbols[16], IMAGE_SYM_CLASS_EXTERNAL);
+            assert_eq!(&symbols[18..26], b"my_add\0\0");
+            assert_eq!(symbols[18 + 16], IMAGE_SYM_CLASS_WEAK_EXTERNAL);
+            assert_eq!(symbols[18 + 17], 1);
+
+            let aux = &symbols[36..54];
+            assert_eq!(NativeEndian::read_u32(&aux[0..4]), 0);
+            assert_eq!(NativeEndian::read_u32(&aux[4..8]), characteristics);
+            assert_eq!(builder.size_report().total(), builder.build().len());
+        }
+    }
+
+    #[test]
+    fn custom_member_names() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .member_name("vendor")
+            .import_name("add", "add")
+            .import_ordinal("mult", 3).import_member_name("mult.obj");
+        let lib = builder.clone().build();
+
+        let names: Vec<_> = parse::Members::new(&lib).unwrap().map(|(name, _)| name).collect();
+        assert_eq!(names, ["/", "/", "vendor/", "vendor/", "vendor/", "vendor/", "mult.obj/"]);
+
+        let mut cursor = io::Cursor::new(vec![]);
+        builder.write_to_seek(&mut cursor).unwrap();
+        assert_eq!(cursor.into_inner(), lib);
+
+        let lib = ImportLibBuilder::new("mydll").target_arch(TargetArch::X86_64).import_name("add", "add").build();
+        assert!(parse::Members::new(&lib).unwrap().skip(2).all(|(name, _)| name == "mydll.dll/"));
+    }
+
+    #[test]
+    fn leading_underscores_are_kept() {
+        for &arch in &[TargetArch::X86, TargetArch::X86_64] {
+            let builder = ImportLibBuilder::new("msvcrt.dll")
+                .target_arch(arch)
+                .import_name("_strdup", "_strdup")
+                .import_rust("strdup", "_strdup");
+            assert!(builder.exported_symbols().contains(&"__imp__strdup".to_owned()));
+
+            let archive = builder.coff_archive().unwrap();
+            let members = &archive.sections[archive.sections.len() - 2..];
+            for member in members {
+                let raw = parse::raw_short_import(member).unwrap();
+                assert_eq!(raw.export_name.unwrap_or(raw.name), "_strdup");
+            }
+
+            // the renamed pair defines `strdup`, so it resolves against the index entry
+            let raw = parse::raw_short_import(&members[1]).unwrap();
+            assert_eq!(raw.name, "strdup");
+            assert_eq!((raw.import_type >> 2) & 0x7, ImportNameType::ExportAs.bits());
+        }
+    }
+
+    #[test]
+    fn builds_are_reproducible() {
+        let build = || {
+            let map: HashMap<_, _> = (0..32u16)
+                .map(|i| (format!("func_{}", i), Import::Ordinal(i + 1)))
+                .collect();
+
+            ImportLibBuilder::from_map("mydll", map)
+                .target_arch(TargetArch::X86)
+                .add_metadata_comment("generated")
+                .import_name("add", "add").as_data()
+                .weak_alias("my_add", "add", WeakSearch::Alias)
+                .member_alignment(8)
+                .build()
+        };
+
+        let (a, b) = (build(), build());
+        assert_eq!(a, b);
+
+        // headers carry the -1 date, objects the 0 default timestamp
+        assert_eq!(&a[ARCHIVE_SIG.len() + 16..ARCHIVE_SIG.len() + 28], b"-1          ");
+        let descriptor = parse::Members::new(&a).unwrap().nth(2).unwrap().1;
+        assert_eq!(NativeEndian::read_u32(&descriptor[4..8]), 0);
+    }
+
+    #[test]
+    fn import_ordinal_range() {
+        let builder = ImportLibBuilder::new("mydll.dll").import_all_ordinals(1..=10);
+
+        assert_eq!(builder.imports.len(), 10);
+        for (entry, ordinal) in builder.imports.iter().zip(1..) {
+            assert_eq!(entry.rust_name, format!("Ordinal_{}", ordinal));
+            assert_eq!(entry.import, Import::Ordinal(ordinal));
+        }
+        assert_eq!(ImportLibBuilder::new("mydll.dll").import_all_ordinals(u16::MAX..=u16::MAX).imports.len(), 1);
+    }
+
+    #[test]
+    fn ordinal_zero_is_rejected() {
+        let builder = ImportLibBuilder::new("mydll.dll").target_arch(TargetArch::X86_64);
+
+        let err = builder.clone().import_all_ordinals(0..=10).try_build().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(builder.clone().import_ordinal("zero", 0).try_build().is_err());
+        assert!(builder.import_ordinal("zero", 0).write_to_seek(&mut io::Cursor::new(vec![])).is_err());
+    }
+
+    #[test]
+    fn descriptor_symbol_collisions_are_rejected() {
+        let builder = ImportLibBuilder::new("foo")
+            .target_arch(TargetArch::X86_64)
+            .import_name("__IMPORT_DESCRIPTOR_foo", "bar");
+
+        // only building rejects them, inspecting the builder still works
+        assert!(builder.size_report().total() > 0);
+        assert!(builder.exported_symbols().contains(&"__imp___IMPORT_DESCRIPTOR_foo".to_owned()));
+
+        let err = builder.clone().try_build().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(builder.write_to_seek(&mut io::Cursor::new(vec![])).is_err());
+
+        let collides = |rust_name: &str| ImportLibBuilder::new("foo")
+            .target_arch(TargetArch::X86_64)
+            .import_name(rust_name, "bar")
+            .try_build()
+            .is_err();
+        assert!(collides("__NULL_IMPORT_DESCRIPTOR"));
+        assert!(collides("\x7Ffoo_NULL_THUNK_DATA"));
+        assert!(!collides("__IMPORT_DESCRIPTOR_bar"));
+
+        // with manual descriptors the caller owns those names
+        assert!(ImportLibBuilder::new("foo")
+            .target_arch(TargetArch::X86_64)
+            .manual_descriptors(true)
+            .import_name("__IMPORT_DESCRIPTOR_foo", "bar")
+            .try_build()
+            .is_ok());
+    }
+
+    #[test]
+    fn only_long_names_reach_string_tables() {
+        let (_, object) = build_import_descriptor("mydll.dll", TargetArch::X86_64, false, 0).unwrap();
+        let symbol_table = NativeEndian::read_u32(&object[8..12]) as usize;
+        let symbols = NativeEndian::read_u32(&object[12..16]) as usize;
+        let string_table = &object[symbol_table + symbols * 18..];
+
+        // the four `.idata$N` section symbols are inlined, 36 bytes the table doesn't need
+        let long_names = ["__IMPORT_DESCRIPTOR_mydll", "__NULL_IMPORT_DESCRIPTOR", "\x7Fmydll_NULL_THUNK_DATA"];
+        let expected = 4 + long_names.iter().map(|name| name.len() + 1).sum::<usize>();
+        assert_eq!(NativeEndian::read_u32(string_table) as usize, expected);
+        assert_eq!(string_table.len(), expected);
+
+        // descriptors are a fixed cost, everything else grows with the imports
+        let report = |count| (0..count)
+            .fold(ImportLibBuilder::new("mydll.dll").target_arch(TargetArch::X86_64), |builder, i| {
+                builder.import_name(&format!("func_{}", i), "func")
+            })
+            .size_report();
+        let (small, large) = (report(10), report(1000));
+        assert_eq!(
+            (small.import_descriptor, small.null_import_descriptor, small.null_thunk_data),
+            (large.import_descriptor, large.null_import_descriptor, large.null_thunk_data)
+        );
+    }
+
+    #[cfg(feature = "build-script")]
+    #[test]
+    fn emits_cargo_directives() {
+        let out_dir = std::env::temp_dir().join(format!("gen_dylib_test_{}", std::process::id()));
+        std::fs::create_dir_all(&out_dir).unwrap();
+
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_name("add", "add");
+        let expected = builder.clone().build();
+
+        let mut stdout = vec![];
+        builder.emit_cargo_directives(&out_dir, "mydll", &mut stdout).unwrap();
+
+        let written = std::fs::read(out_dir.join("mydll.lib")).unwrap();
+        std::fs::remove_dir_all(&out_dir).unwrap();
+
+        assert_eq!(written, expected);
+        assert_eq!(String::from_utf8(stdout).unwrap(), format!(
+            "cargo:rustc-link-search=native={}\ncargo:rustc-link-lib=dylib=mydll\n",
+            out_dir.display()
+        ));
+    }
+
+    #[test]
+    fn import_member_order() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_ordinal("mult", 7)
+            .import_name("sub", "sub")
+            .import_ordinal("div", 2)
+            .import_name("add", "add")
+            .import_ordinal("neg", 4);
+
+        let imports = |builder: ImportLibBuilder| -> Vec<Import> {
+            parse::Members::new(&builder.build()).unwrap()
+                .filter_map(|(_, data)| parse::parse_short_import(data).ok())
+                .map(|(_, import, _)| import)
+                .collect()
+        };
+        let name = |s: &str| Import::Name(s.to_owned());
+
+        assert_eq!(imports(builder.clone()), [
+            Import::Ordinal(7), name("sub"), Import::Ordinal(2), name("add"), Import::Ordinal(4),
+        ]);
+        assert_eq!(imports(builder.clone().member_order(MemberOrder::ByOrdinal)), [
+            Import::Ordinal(2), Import::Ordinal(4), Import::Ordinal(7), name("sub"), name("add"),
+        ]);
+        assert_eq!(imports(builder.clone().member_order(MemberOrder::ByName)), [
+            name("add"), Import::Ordinal(2), Import::Ordinal(7), Import::Ordinal(4), name("sub"),
+        ]);
+        assert_eq!(imports(builder.clone().member_order(MemberOrder::Custom(|a, b| b.0.cmp(a.0)))), [
+            name("sub"), Import::Ordinal(4), Import::Ordinal(7), Import::Ordinal(2), name("add"),
+        ]);
+
+        // the linker member still points every `__imp_` symbol at its own member
+        let lib = builder.member_order(MemberOrder::ByOrdinal).build();
+        let (first, _) = parse_linker_members(&lib).unwrap();
+        for (symbol, offset) in first.symbols.iter().zip(first.offsets) {
+            if let Some(rust_name) = symbol.strip_prefix("__imp_") {
+                let (name, ..) = parse::parse_short_import(&lib[offset as usize + ARCHIVE_HEADER_LEN..]).unwrap();
+                assert_eq!(name, rust_name);
+            }
+        }
+    }
+
+    #[test]
+    fn from_bytes_rejects_mixed_dlls() {
+        let arch = TargetArch::X86_64;
+        let other = short_import_object("other.dll", "sub", &Import::Name("sub".to_owned()), arch);
+        // skips debug_verify, which rejects the foreign member as well
+        let lib = build_library(ImportLibBuilder::new("mydll.dll")
+            .target_arch(arch)
+            .add_object(other, &["sub", "__imp_sub"])
+            .import_name("add", "add")).unwrap();
+
+        assert_eq!(
+            ImportLibBuilder::from_bytes(&lib).unwrap_err(),
+            ParseError::MixedDlls("other.dll".to_owned(), "mydll.dll".to_owned()),
+        );
+    }
+
+    #[test]
+    fn unknown_members_survive_round_trip() {
+        // a `.drectve` object, recognized by neither the builder nor the parser
+        let mut drectve = build_comment_object("/DEFAULTLIB:foo ", TargetArch::X86_64, 0).unwrap();
+        drectve[COFF_HEADER_LEN..COFF_HEADER_LEN + 8].copy_from_slice(b".drectve");
+
+        let lib = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .add_object(drectve.clone(), &[])
+            .weak_alias("my_add", "add", WeakSearch::Alias)
+            .import_name("add", "add")
+            .import_ordinal("mult", 3)
+            .build();
+
+        let parsed = ImportLibBuilder::from_bytes(&lib).unwrap();
+        assert_eq!(parsed.objects.len(), 2);
+        assert_eq!(parsed.objects[0].data, drectve);
+        assert_eq!(parsed.objects[1].symbols, ["my_add"]);
+        assert_eq!(parsed.clone().build(), lib);
+
+        // a long names table stays right behind the linker members, member names untouched
+        let mut parsed = parsed;
+        parsed.long_names = Some(b"a_very_long_member_name.obj/\n".to_vec());
+        parsed.objects[0].header_name = Some("/0".to_owned());
+        let lib = parsed.build();
+
+        let names: Vec<_> = parse::Members::new(&lib).unwrap().map(|(name, _)| name).collect();
+        assert_eq!(names[..4], ["/", "/", "//", "mydll.dll/"]);
+        assert!(names.contains(&"/0"));
+
+        let reparsed = ImportLibBuilder::from_bytes(&lib).unwrap();
+        assert_eq!(reparsed.size_report().total(), lib.len());
+        assert_eq!(reparsed.build(), lib);
+
+        // lib wide and per import settings come back from the members
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .member_alignment(8)
+            .pad_final_member(false)
+            .kernel_mode(true)
+            .timestamp(0x5000_0000)
+            .member_name("mylib")
+            .import_name("add", "add").import_timestamp(0x6000_0001)
+            .import_ordinal("mult", 3).import_member_name("mult.obj")
+            .import_name("sub", "sub");
+        let lib = builder.clone().build();
+
+        let parsed = ImportLibBuilder::from_bytes(&lib).unwrap();
+        assert_eq!(parsed.member_alignment, 8);
+        assert!(!parsed.pad_final_member);
+        assert!(parsed.kernel_mode);
+        assert_eq!(parsed.timestamp, 0x5000_0000);
+        assert_eq!(parsed.member_name.as_deref(), Some("mylib"));
+        assert_eq!(parsed.imports[0].timestamp, Some(0x6000_0001));
+        assert_eq!(parsed.imports[1].header_name.as_deref(), Some("mult.obj/"));
+        assert_eq!((parsed.imports[2].timestamp, parsed.imports[2].header_name.as_ref()), (None, None));
+        assert_eq!(parsed.build(), lib);
+
+        // the default layout round trips too, including the pad byte after an odd sized import
+        let builder = builder.member_alignment(2).pad_final_member(true).import_name("xy", "xy");
+        let lib = builder.build();
+        let parsed = ImportLibBuilder::from_bytes(&lib).unwrap();
+        assert!(parsed.pad_final_member);
+        assert_eq!(parsed.build(), lib);
+    }
+
+    #[test]
+    fn descriptor_section_headers_are_laid_out() {
+        assert_eq!(COFF_SECTION_HEADER_LEN, 40);
+
+        let arch = TargetArch::X86_64;
+        let objects = vec![
+            build_import_descriptor("mydll.dll", arch, false, 0).unwrap().1,
+            build_null_import_descriptor(arch, false, 0).unwrap().1,
+            build_null_thunk_data("mydll.dll", arch, false, 0).unwrap().1,
+        ];
+
+        for object in &objects {
+            let sections = NativeEndian::read_u16(&object[2..4]) as usize;
+            let symbol_table = NativeEndian::read_u32(&object[8..12]) as usize;
+            let headers_end = COFF_HEADER_LEN + sections * COFF_SECTION_HEADER_LEN;
+
+            let mut data_end = headers_end;
+            for i in 0..sections {
+                let header = &object[COFF_HEADER_LEN + i * COFF_SECTION_HEADER_LEN..];
+                assert!(header.starts_with(b".idata$"));
+
+                // raw data follows the headers back to back, in section order
+                let size = NativeEndian::read_u32(&header[16..20]) as usize;
+                let raw_data = NativeEndian::read_u32(&header[20..24]) as usize;
+                assert!(raw_data >= data_end);
+                data_end = raw_data + size;
+            }
+            assert!(data_end <= symbol_table);
+        }
+
+        // the second section of the import descriptor holds the dll name
+        let header = &objects[0][COFF_HEADER_LEN + COFF_SECTION_HEADER_LEN..];
+        assert_eq!(&header[0..8], b".idata$6");
+        let raw_data = NativeEndian::read_u32(&header[20..24]) as usize;
+        assert_eq!(&objects[0][raw_data..raw_data + 10], b"mydll.dll\0");
+    }
+
+    #[cfg(feature = "debug_verify")]
+    #[test]
+    #[should_panic(expected = "built an inconsistent import lib")]
+    fn debug_verify_catches_corrupt_build() {
+        // the NUL ends the name string early, so the member no longer matches the builder
+        ImportLibBuilder::new("mydll.dll")
+            .import_name("a\0b", "a\0b")
+            .build();
+    }
+
+    #[cfg(feature = "debug_verify")]
+    #[test]
+    #[should_panic(expected = "built an inconsistent import lib")]
+    fn debug_verify_catches_corrupt_write_to_seek() {
+        ImportLibBuilder::new("mydll.dll")
+            .import_name("a\0b", "a\0b")
+            .write_to_seek(&mut io::Cursor::new(vec![]))
+            .unwrap();
+    }
+}
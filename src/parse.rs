@@ -0,0 +1,321 @@
+use byteorder::{BigEndian, ByteOrder, NativeEndian};
+use std::error::Error;
+use std::fmt;
+
+use crate::{Import, ImportKind, ImportNameType, TargetArch, ARCHIVE_HEADER_LEN, ARCHIVE_SIG, SHORT_IMPORT_HEADER_LEN};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    NotAnArchive,
+    MissingLinkerMember,
+    Truncated,
+    InvalidName,
+    NotShortImport,
+    UnsupportedImportType(u16),
+    NoImports,
+    /// Members import from different dlls, as `(first, other)`.
+    MixedDlls(String, String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::NotAnArchive => write!(f, "missing archive signature"),
+            ParseError::MissingLinkerMember => write!(f, "missing linker member"),
+            ParseError::Truncated => write!(f, "unexpected end of data"),
+            ParseError::InvalidName => write!(f, "symbol name is not valid utf-8"),
+            ParseError::NotShortImport => write!(f, "member is not a short import"),
+            ParseError::UnsupportedImportType(ty) => write!(f, "unsupported import type {:#x}", ty),
+            ParseError::NoImports => write!(f, "archive has no short import members"),
+            ParseError::MixedDlls(first, other) => write!(f, "archive imports from both `{}` and `{}`", first, other),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FirstLinkerMember {
+    pub offsets: Vec<u32>,
+    pub symbols: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecondLinkerMember {
+    pub offsets: Vec<u32>,
+    pub indices: Vec<u16>,
+    pub symbols: Vec<String>,
+}
+
+pub(crate) struct Members<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Members<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Option<Self> {
+        if !bytes.starts_with(ARCHIVE_SIG) {
+            return None;
+        }
+
+        Some(Self {
+            bytes,
+            pos: ARCHIVE_SIG.len(),
+        })
+    }
+
+    // like `next`, also returning the offset of the member's header
+    pub(crate) fn next_with_offset(&mut self) -> Option<(usize, &'a str, &'a [u8])> {
+        let offset = self.pos;
+        let header = self.bytes.get(offset..offset + ARCHIVE_HEADER_LEN)?;
+        let name = std::str::from_utf8(&header[0..16]).ok()?.trim_end();
+        let size = std::str::from_utf8(&header[48..58]).ok()?.trim_end().parse::<usize>().ok()?;
+
+        let start = offset + ARCHIVE_HEADER_LEN;
+        let data = self.bytes.get(start..start + size)?;
+
+        self.pos = start + size;
+        if self.pos % 2 != 0 {
+            self.pos += 1;
+        }
+
+        Some((offset, name, data))
+    }
+}
+
+impl<'a> Iterator for Members<'a> {
+    type Item = (&'a str, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_offset().map(|(_, name, data)| (name, data))
+    }
+}
+
+pub fn parse_linker_members(bytes: &[u8]) -> Result<(FirstLinkerMember, SecondLinkerMember), ParseError> {
+    let mut members = Members::new(bytes).ok_or(ParseError::NotAnArchive)?;
+
+    let mut linker_member = || match members.next() {
+        Some(("/", data)) => Ok(data),
+        _ => Err(ParseError::MissingLinkerMember),
+    };
+
+    let first = linker_member()?;
+    let second = linker_member()?;
+
+    Ok((parse_first_linker_member(first)?, parse_second_linker_member(second)?))
+}
+
+pub(crate) fn parse_first_linker_member(data: &[u8]) -> Result<FirstLinkerMember, ParseError> {
+    let mut reader = Reader(data);
+
+    let symbol_count = BigEndian::read_u32(reader.take(4)?) as usize;
+    let offsets = (0..symbol_count)
+        .map(|_| reader.take(4).map(BigEndian::read_u32))
+        .collect::<Result<_, _>>()?;
+    let symbols = reader.strings(symbol_count)?;
+
+    Ok(FirstLinkerMember { offsets, symbols })
+}
+
+fn parse_second_linker_member(data: &[u8]) -> Result<SecondLinkerMember, ParseError> {
+    let mut reader = Reader(data);
+
+    let member_count = NativeEndian::read_u32(reader.take(4)?) as usize;
+    let offsets = (0..member_count)
+        .map(|_| reader.take(4).map(NativeEndian::read_u32))
+        .collect::<Result<_, _>>()?;
+
+    let symbol_count = NativeEndian::read_u32(reader.take(4)?) as usize;
+    let indices = (0..symbol_count)
+        .map(|_| reader.take(2).map(NativeEndian::read_u16))
+        .collect::<Result<_, _>>()?;
+    let symbols = reader.strings(symbol_count)?;
+
+    Ok(SecondLinkerMember { offsets, indices, symbols })
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ParseError> {
+        if self.0.len() < len {
+            return Err(ParseError::Truncated);
+        }
+
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn strings(&mut self, count: usize) -> Result<Vec<String>, ParseError> {
+        (0..count).map(|_| {
+            let len = self.0.iter().position(|&b| b == b'\0').ok_or(ParseError::Truncated)?;
+            let name = std::str::from_utf8(self.take(len)?).map_err(|_| ParseError::InvalidName)?;
+            self.take(1)?;
+            Ok(name.to_owned())
+        }).collect()
+    }
+}
+
+pub(crate) struct RawShortImport<'a> {
+    pub(crate) machine: u16,
+    pub(crate) timestamp: u32,
+    pub(crate) ordinal_or_hint: u16,
+    pub(crate) import_type: u16,
+    pub(crate) name: &'a str,
+    pub(crate) dll_name: &'a str,
+    // only present for IMPORT_OBJECT_NAME_EXPORTAS
+    pub(crate) export_name: Option<&'a str>,
+}
+
+impl RawShortImport<'_> {
+    // the Type field holds the kind in bits 0-1 and the name type in bits 2-4
+    pub(crate) fn kind(&self) -> Result<ImportKind, ParseError> {
+        ImportKind::from_bits(self.import_type & 0x3).ok_or(ParseError::UnsupportedImportType(self.import_type))
+    }
+
+    pub(crate) fn name_type(&self) -> Result<ImportNameType, ParseError> {
+        ImportNameType::from_bits((self.import_type >> 2) & 0x7).ok_or(ParseError::UnsupportedImportType(self.import_type))
+    }
+}
+
+pub(crate) fn raw_short_import(member: &[u8]) -> Result<RawShortImport<'_>, ParseError> {
+    if member.get(0..4).ok_or(ParseError::Truncated)? != [0x00, 0x00, 0xFF, 0xFF] {
+        return Err(ParseError::NotShortImport);
+    }
+
+    let mut reader = Reader(member);
+    let mut header = Reader(reader.take(SHORT_IMPORT_HEADER_LEN)?);
+
+    header.take(4)?; // Sig1, Sig2
+    header.take(2)?; // Version
+    let machine = NativeEndian::read_u16(header.take(2)?);
+    let timestamp = NativeEndian::read_u32(header.take(4)?);
+    let size = NativeEndian::read_u32(header.take(4)?) as usize;
+    let ordinal_or_hint = NativeEndian::read_u16(header.take(2)?);
+    let import_type = NativeEndian::read_u16(header.take(2)?);
+
+    let mut strings = Reader(reader.take(size)?);
+    let mut string = || {
+        let len = strings.0.iter().position(|&b| b == b'\0').ok_or(ParseError::Truncated)?;
+        let s = std::str::from_utf8(strings.take(len)?).map_err(|_| ParseError::InvalidName)?;
+        strings.take(1)?;
+        Ok(s)
+    };
+
+    let name = string()?;
+    let dll_name = string()?;
+    let export_name = if (import_type >> 2) & 0x7 == ImportNameType::ExportAs.bits() {
+        Some(string()?)
+    } else {
+        None
+    };
+
+    Ok(RawShortImport {
+        machine,
+        timestamp,
+        ordinal_or_hint,
+        import_type,
+        name,
+        dll_name,
+        export_name,
+    })
+}
+
+/// Decodes a short import member into the symbol name it defines, the `Import` it describes
+/// and the DLL name it embeds. The symbol and export names only differ for ordinal and
+/// `IMPORT_OBJECT_NAME_EXPORTAS` imports.
+pub fn parse_short_import(member: &[u8]) -> Result<(String, Import, String), ParseError> {
+    let raw = raw_short_import(member)?;
+    raw.kind()?;
+
+    let import = match raw.name_type()? {
+        ImportNameType::Ordinal => Import::Ordinal(raw.ordinal_or_hint),
+        _ => Import::Name(raw.export_name.unwrap_or(raw.name).to_owned()),
+    };
+
+    Ok((raw.name.to_owned(), import, raw.dll_name.to_owned()))
+}
+
+pub fn detected_arch(bytes: &[u8]) -> Option<TargetArch> {
+    let (_, member) = Members::new(bytes)?.find(|(name, _)| *name != "/" && *name != "//")?;
+
+    let machine = match raw_short_import(member) {
+        Ok(import) => import.machine,
+        Err(ParseError::NotShortImport) => NativeEndian::read_u16(member.get(0..2)?),
+        Err(_) => return None,
+    };
+
+    TargetArch::from_machine(machine)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ImportLibBuilder;
+
+    #[test]
+    fn detects_built_arch() {
+        for &arch in &[TargetArch::X86, TargetArch::X86_64] {
+            let lib = ImportLibBuilder::new("mydll.dll")
+                .target_arch(arch)
+                .import_name("add", "add")
+                .build();
+
+            assert_eq!(detected_arch(&lib), Some(arch));
+        }
+
+        assert_eq!(detected_arch(b"not an archive"), None);
+    }
+
+    #[test]
+    fn linker_members_match_builder() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_ordinal("mult", 3)
+            .import_name("add", "add")
+            .import_name("sub", "sub");
+        let exported = builder.exported_symbols();
+
+        let (first, second) = parse_linker_members(&builder.build()).unwrap();
+        assert_eq!(first.symbols, exported);
+        assert_eq!(first.offsets.len(), exported.len());
+
+        let mut sorted = second.symbols.clone();
+        sorted.sort();
+        let mut expected = exported;
+        expected.sort();
+        assert_eq!(sorted, expected);
+        assert_eq!(second.indices.len(), second.symbols.len());
+        assert_eq!(second.offsets.len(), 6);
+
+        assert_eq!(parse_linker_members(b"!<arch>\n"), Err(ParseError::MissingLinkerMember));
+        assert_eq!(parse_linker_members(b"not an archive"), Err(ParseError::NotAnArchive));
+    }
+
+    #[test]
+    fn short_imports_round_trip() {
+        let builder = ImportLibBuilder::new("mydll")
+            .target_arch(TargetArch::X86)
+            .import_ordinal("mult", 3)
+            .import_name("add", "add").as_data()
+            .import_name("sub", "_sub@8").as_const();
+        let lib = builder.build();
+
+        let imports: Vec<_> = Members::new(&lib).unwrap()
+            .filter_map(|(_, data)| parse_short_import(data).ok())
+            .collect();
+        assert_eq!(imports, vec![
+            ("mult".to_owned(), Import::Ordinal(3), "mydll.dll".to_owned()),
+            ("add".to_owned(), Import::Name("add".to_owned()), "mydll.dll".to_owned()),
+            ("sub".to_owned(), Import::Name("_sub@8".to_owned()), "mydll.dll".to_owned()),
+        ]);
+
+        let descriptor = Members::new(&lib).unwrap().nth(2).unwrap().1;
+        assert_eq!(parse_short_import(descriptor), Err(ParseError::NotShortImport));
+
+        let mut member = crate::short_import_object("mydll", "add", &Import::Name("add".to_owned()), TargetArch::X86);
+        member[18] = 0x3;
+        assert_eq!(parse_short_import(&member), Err(ParseError::UnsupportedImportType(0x3)));
+    }
+}
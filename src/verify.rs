@@ -0,0 +1,76 @@
+use crate::parse::{self, Members};
+use crate::{dll_file_name, Import, ImportLibBuilder};
+
+// checks a built lib against the builder it came from
+pub(crate) fn verify(builder: &ImportLibBuilder, lib: &[u8]) -> Result<(), String> {
+    // the first linker member is shared by the MSVC and GNU symbol table formats
+    let first = match Members::new(lib).ok_or("missing archive signature")?.next() {
+        Some(("/", data)) => parse::parse_first_linker_member(data).map_err(|e| e.to_string())?,
+        _ => return Err("missing linker member".to_owned()),
+    };
+
+    let expected = builder.exported_symbols();
+    if first.symbols != expected {
+        return Err(format!("linker member lists {:?}, expected {:?}", first.symbols, expected));
+    }
+
+    for offset in &first.offsets {
+        let offset = *offset as usize;
+        if lib.get(offset + 58..offset + 60) != Some(b"`\n") {
+            return Err(format!("symbol offset {:#x} is not a member header", offset));
+        }
+    }
+
+    let dll_name = dll_file_name(&builder.name);
+    let imports: Vec<_> = Members::new(lib).ok_or("missing archive signature")?
+        .filter_map(|(_, data)| parse::raw_short_import(data).ok())
+        .collect();
+
+    if imports.len() != builder.imports.len() {
+        return Err(format!("found {} imports, expected {}", imports.len(), builder.imports.len()));
+    }
+
+    for (raw, entry) in imports.iter().zip(builder.ordered_imports()) {
+        if raw.dll_name != dll_name {
+            return Err(format!("`{}` imports from {}, expected {}", entry.rust_name, raw.dll_name, dll_name));
+        }
+
+        // the linker defines the symbols from the name string, the export may follow the dll
+        let name_type = (raw.import_type >> 2) & 0x7;
+        let matches = raw.name == entry.rust_name && match &entry.import {
+            Import::Name(name) => raw.export_name.unwrap_or(raw.name) == name && name_type != 0,
+            Import::Ordinal(ordinal) => raw.ordinal_or_hint == *ordinal && name_type == 0,
+        };
+
+        if !matches {
+            return Err(format!("`{}` was emitted as `{}` (type {:#x})", entry.rust_name, raw.name, raw.import_type));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetArch;
+
+    #[test]
+    fn verifies_built_libs() {
+        let builder = ImportLibBuilder::new("mydll.dll")
+            .target_arch(TargetArch::X86_64)
+            .import_ordinal("mult", 3)
+            .import_name("add", "add");
+
+        let mut lib = builder.clone().build();
+        assert_eq!(verify(&builder, &lib), Ok(()));
+
+        // point `mult` at the wrong ordinal
+        let mult = lib.windows(5).position(|w| w == b"mult\0").unwrap();
+        lib[mult - 4] = 4;
+        assert!(verify(&builder, &lib).is_err());
+
+        let other = builder.clone().import_name("sub", "sub");
+        assert!(verify(&other, &builder.build()).is_err());
+    }
+}